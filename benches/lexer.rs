@@ -0,0 +1,42 @@
+//! Measures how the lexer scales with the size of the input.
+//!
+//! Run with `cargo bench --bench lexer`. The time per line should stay
+//! roughly the same as the input grows, since the lexer only walks
+//! the source once.
+
+use std::time::{Duration, Instant};
+
+use nexus_lib::lexer::Lexer;
+
+const SAMPLE: &str = r#"counter := 1_000
+name :: "entertaining cats {counter}"
+total := counter * 2 + 3.5 / 7
+is_big := total >= 100 # compare against a threshold
+print(name, total, is_big)
+"#;
+
+const RUNS: u32 = 5;
+
+fn main() {
+    println!("{:>8} {:>14} {:>14}", "lines", "total", "per line");
+    for scale in [250, 500, 1_000, 2_000, 4_000] {
+        let source = SAMPLE.repeat(scale);
+        let lines = source.lines().count();
+        let elapsed = (0..RUNS).map(|_| lex(&source)).sum::<Duration>() / RUNS;
+        println!(
+            "{:>8} {:>14?} {:>14?}",
+            lines,
+            elapsed,
+            elapsed / lines as u32
+        );
+    }
+}
+
+fn lex(source: &str) -> Duration {
+    let lexer = Lexer::from_string(source, Some("bench.nx"));
+    let start = Instant::now();
+    for tok in lexer {
+        tok.expect("Failed to lex");
+    }
+    start.elapsed()
+}
@@ -1,21 +1,37 @@
-use std::{collections::HashMap, ops::{Deref, DerefMut}};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
 use super::objects::Object;
 
 #[derive(Debug)]
 pub struct Environment {
     store: HashMap<String, EnvObj>,
+    /// The scope this scope is nested in
+    outer: Option<Rc<RefCell<Environment>>>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
+pub enum AssignError {
+    /// There is no binding with the key
+    Undefined,
+    /// The binding was declared as a constant
+    Const,
+}
+
+#[derive(Debug, Clone)]
 pub struct EnvObj {
     pub obj: Object,
     pub is_const: bool,
+    /// Local bindings cannot be imported by other files
+    pub is_local: bool,
 }
 
 impl EnvObj {
     pub fn new(obj: Object, is_const: bool) -> Self {
-        Self { obj, is_const }
+        Self {
+            obj,
+            is_const,
+            is_local: false,
+        }
     }
 }
 
@@ -29,14 +45,53 @@ impl Environment {
     pub fn new() -> Self {
         Self {
             store: HashMap::new(),
+            outer: None,
         }
     }
 
+    /// Create a scope whose bindings are dropped when it is left.
+    /// Bindings of the outer scope are still visible
+    pub fn new_enclosed(outer: Rc<RefCell<Environment>>) -> Self {
+        Self {
+            store: HashMap::new(),
+            outer: Some(outer),
+        }
+    }
+
+    /// Redeclaring a local binding keeps it local
     pub fn set(&mut self, key: String, obj: EnvObj) {
-        self.store.insert(key, obj);
+        let is_local = obj.is_local || self.store.get(&key).is_some_and(|old| old.is_local);
+        self.store.insert(key, EnvObj { is_local, ..obj });
+    }
+
+    /// Changes the value of an existing binding in the
+    /// innermost scope that contains the key
+    pub fn assign(&mut self, key: &String, obj: Object) -> Result<(), AssignError> {
+        match self.store.get_mut(key) {
+            Some(EnvObj { is_const: true, .. }) => Err(AssignError::Const),
+            Some(env_obj) => {
+                env_obj.obj = obj;
+                Ok(())
+            }
+            None => match &self.outer {
+                Some(outer) => outer.borrow_mut().assign(key, obj),
+                None => Err(AssignError::Undefined),
+            },
+        }
     }
 
-    pub fn get(&self, key: &String) -> Option<&EnvObj> {
-        self.store.get(key)
+    /// Marks a binding of this scope as local
+    pub fn set_local(&mut self, key: &String) {
+        if let Some(env_obj) = self.store.get_mut(key) {
+            env_obj.is_local = true;
+        }
+    }
+
+    /// Looks up the key in this scope and then in the outer scopes
+    pub fn get(&self, key: &String) -> Option<EnvObj> {
+        match self.store.get(key) {
+            Some(obj) => Some(obj.clone()),
+            None => self.outer.as_ref()?.borrow().get(key),
+        }
     }
 }
@@ -0,0 +1,97 @@
+use std::{collections::HashMap, fmt::Debug, path::PathBuf, rc::Rc};
+
+use crate::{
+    lexer::source::{FsProvider, SourceProvider},
+    parser::ast::Ident,
+};
+
+use super::objects::ModuleObj;
+
+/// Maps `use` paths to source files and keeps every
+/// module that was evaluated, so it is only evaluated once
+pub struct ModuleLoader {
+    /// Where the files of the modules are read from
+    provider: Box<dyn SourceProvider>,
+    /// Directories modules are searched in, in order
+    roots: Vec<PathBuf>,
+    /// Evaluated modules by their path like `a.b`
+    cache: HashMap<String, Rc<ModuleObj>>,
+    /// Paths of the modules that are currently evaluated.
+    /// Importing one of them again would be circular
+    loading: Vec<String>,
+}
+
+impl Default for ModuleLoader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Debug for ModuleLoader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ModuleLoader")
+            .field("roots", &self.roots)
+            .field("cache", &self.cache)
+            .field("loading", &self.loading)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ModuleLoader {
+    /// Create a loader that reads modules from the filesystem
+    pub fn new() -> Self {
+        Self::with_provider(FsProvider)
+    }
+
+    pub fn with_provider(provider: impl SourceProvider + 'static) -> Self {
+        Self {
+            provider: Box::new(provider),
+            roots: Vec::new(),
+            cache: HashMap::new(),
+            loading: Vec::new(),
+        }
+    }
+
+    pub fn provider(&self) -> &dyn SourceProvider {
+        self.provider.as_ref()
+    }
+
+    /// Add a directory to search for modules. Roots
+    /// that were added first are searched first
+    pub fn add_root(&mut self, root: impl Into<PathBuf>) {
+        self.roots.push(root.into());
+    }
+
+    pub fn get(&self, path: &str) -> Option<Rc<ModuleObj>> {
+        self.cache.get(path).cloned()
+    }
+
+    pub fn insert(&mut self, module: Rc<ModuleObj>) {
+        self.cache.insert(module.path.clone(), module);
+    }
+
+    /// Finds the file of the module in the first root that
+    /// contains it. The path `a.b` is the file `a/b.nx`
+    pub fn resolve(&self, path: &[Ident]) -> Option<PathBuf> {
+        let mut rel_path: PathBuf = path.iter().map(|part| part.0.as_str()).collect();
+        rel_path.set_extension("nx");
+        self.roots
+            .iter()
+            .map(|root| root.join(&rel_path))
+            .find(|file| self.provider.exists(file))
+    }
+
+    /// Marks the module as being evaluated until `finish` is called
+    pub fn start(&mut self, path: &str) {
+        if let Some(pos) = self.loading.iter().position(|loading| loading == path) {
+            let mut chain = self.loading[pos..].to_vec();
+            chain.push(path.into());
+            panic!("Circular import of module {}: {}", path, chain.join(" -> "));
+        }
+        self.loading.push(path.into());
+    }
+
+    pub fn finish(&mut self) {
+        self.loading.pop();
+    }
+}
@@ -1,25 +1,47 @@
-use std::{
-    cell::{RefCell, RefMut},
-    f32::consts::E,
-    rc::Rc,
-};
+use std::{cell::RefCell, mem::replace, rc::Rc};
 
-use crate::parser::ast::{
-    BlockStmt, CallExpr, Expression, FuncExpr, Ident, InfixExpr, InfixOp, Literal, PrefixExpr,
-    PrefixOp, Statement, VarStmt,
+use crate::{
+    lexer::Lexer,
+    parser::{
+        ast::{
+            BlockStmt, CallExpr, CaseResult, EnumExpr, Expression, FieldExpr, FuncExpr, Ident,
+            IfExpr, IndexExpr, InfixExpr, InfixOp, InstanceExpr, InterpolatedExpr, ListExpr,
+            ListPattern, Literal, LoopExpr, LoopType, Pattern, PrefixExpr, PrefixOp, Program,
+            RangePattern, Statement, StrPart, StructExpr, StructPattern, UseStmt, VarStmt,
+            VariantPattern, WhenExpr,
+        },
+        Parser,
+    },
 };
 use builtins::{BuiltinFunc, Input, Print};
-use env::{EnvObj, Environment};
-use objects::{Comparable, FuncObj, Object};
+use env::{AssignError, EnvObj, Environment};
+use loader::ModuleLoader;
+use objects::{
+    Comparable, EnumObj, FuncObj, InstanceObj, ModuleObj, Object, RangeObj, StructObj, VariantObj,
+};
+
+/// Returns the value from the current function if it is a
+/// signal, so it unwinds to the surrounding loop or function
+macro_rules! unwind {
+    ($val:expr) => {
+        match $val {
+            val if val.is_signal() => return val,
+            val => val,
+        }
+    };
+}
 
 pub mod builtins;
 pub mod env;
+pub mod loader;
 pub mod objects;
 mod tests;
 
 #[derive(Debug)]
 pub struct Evaluator {
     pub env: Rc<RefCell<Environment>>,
+    /// Resolves and caches the modules that are imported
+    pub loader: ModuleLoader,
 }
 
 impl Default for Evaluator {
@@ -32,48 +54,309 @@ impl Evaluator {
     pub fn new() -> Self {
         Self {
             env: Rc::new(RefCell::new(Environment::new())),
+            loader: ModuleLoader::new(),
+        }
+    }
+
+    /// Evaluates the program in its own scope and makes
+    /// its bindings importable with `use` under the path
+    pub fn add_module(&mut self, path: &str, program: Program) -> Rc<ModuleObj> {
+        let env = Rc::new(RefCell::new(Environment::new()));
+        let outer = replace(&mut self.env, Rc::clone(&env));
+        self.eval_program(program);
+        self.env = outer;
+
+        let module = Rc::new(ModuleObj {
+            path: path.into(),
+            env,
+        });
+        self.loader.insert(Rc::clone(&module));
+        module
+    }
+
+    /// Returns the cached module or evaluates the file
+    /// the path resolves to in one of the search roots
+    fn load_module(&mut self, path: &[Ident]) -> Rc<ModuleObj> {
+        let name: Vec<_> = path.iter().map(|part| part.0.as_str()).collect();
+        let name = name.join(".");
+        if let Some(module) = self.loader.get(&name) {
+            return module;
         }
+
+        let file = match self.loader.resolve(path) {
+            Some(file) => file,
+            None => panic!("Could not find module: {}", name),
+        };
+        let mut lexer = Lexer::from_provider(self.loader.provider(), file)
+            .unwrap_or_else(|err| panic!("Failed to read module {}: {}", name, err));
+        let program = match Parser::new(&mut lexer).parse_program() {
+            Ok(program) => program,
+            Err(errors) => {
+                let errors: Vec<_> = errors.iter().map(|err| err.to_string()).collect();
+                panic!("Failed to parse module {}:\n{}", name, errors.join("\n"))
+            }
+        };
+
+        self.loader.start(&name);
+        let module = self.add_module(&name, program);
+        self.loader.finish();
+        module
+    }
+
+    /// Evaluate every statement of the program in order.
+    /// Returns the value of the last statement
+    pub fn eval_program(&mut self, program: Program) -> Object {
+        let mut val = Object::Void;
+        for stmt in program.stmts {
+            val = match self.eval_stmt(stmt) {
+                // Returning ends the program
+                Object::Ret(val) => return *val,
+                Object::Br(..) => panic!("Cannot break outside of a loop"),
+                Object::Cont(_) => panic!("Cannot continue outside of a loop"),
+                val => val,
+            };
+        }
+        val
     }
 
     pub fn eval_stmt(&mut self, stmt: Statement) -> Object {
         match stmt {
             Statement::Variable(node) => self.eval_var(node),
-            Statement::Return(_) => todo!(),
-            Statement::Break(_) => todo!(),
-            Statement::Local(_) => todo!(),
-            Statement::Use(_) => todo!(),
+            Statement::Return(node) => {
+                let val = match node.val {
+                    Some(val) => unwind!(self.eval_expr(val)),
+                    None => Object::Void,
+                };
+                Object::Ret(Box::new(val))
+            }
+            Statement::Break(node) => {
+                let val = match node.val {
+                    Some(val) => unwind!(self.eval_expr(val)),
+                    None => Object::Void,
+                };
+                Object::Br(node.label, Box::new(val))
+            }
+            Statement::Continue(node) => Object::Cont(node.label),
+            Statement::Local(node) => self.eval_local(*node.val),
+            Statement::Use(node) => self.eval_use(node),
             Statement::Expression(node) => self.eval_expr(node),
         }
     }
 
+    /// Binds the module or the selected bindings
+    /// of it as constants in the current scope
+    fn eval_use(&mut self, node: UseStmt) -> Object {
+        let module = self.load_module(&node.path);
+        let names = Self::use_bindings(&node);
+
+        let mut env = self.env.borrow_mut();
+        match node.items {
+            Some(_) => {
+                for name in names {
+                    let obj = Self::get_export(&module, &name);
+                    env.set(name.0, EnvObj::new(obj, true));
+                }
+            }
+            None => {
+                for name in names {
+                    env.set(name.0, EnvObj::new(Object::Use(Rc::clone(&module)), true));
+                }
+            }
+        }
+        Object::Void
+    }
+
+    /// Names that a use statement binds in the current scope
+    fn use_bindings(node: &UseStmt) -> Vec<Ident> {
+        match (&node.items, &node.alias) {
+            (Some(items), _) => items.clone(),
+            (None, Some(alias)) => vec![alias.clone()],
+            (None, None) => vec![node.path.last().expect("Paths are never empty").clone()],
+        }
+    }
+
+    /// Looks up a binding that other files are allowed to import
+    fn get_export(module: &ModuleObj, name: &Ident) -> Object {
+        match module.get(name) {
+            Some(EnvObj { is_local: true, .. }) => {
+                panic!("{} is local to the module {}", name, module.path)
+            }
+            Some(env_obj) => env_obj.obj,
+            None => panic!("Module {} has no binding {}", module.path, name),
+        }
+    }
+
+    /// Evaluates the binding and hides it from files that import this one
+    fn eval_local(&mut self, stmt: Statement) -> Object {
+        let names = match &stmt {
+            Statement::Variable(node) => vec![node.name.ident.clone()],
+            Statement::Use(node) => Self::use_bindings(node),
+            other => panic!("Only bindings can be local, got: {}", other),
+        };
+        let val = self.eval_stmt(stmt);
+        let mut env = self.env.borrow_mut();
+        for name in names {
+            env.set_local(&name.0);
+        }
+        val
+    }
+
     fn eval_expr(&mut self, expr: Expression) -> Object {
         match expr {
             Expression::Ident(node) => self.eval_ident(node),
             Expression::Literal(node) => Object::Lit(node),
+            Expression::Interpolated(node) => self.eval_interpolated(node),
             Expression::Prefix(node) => self.eval_prefix(node),
             Expression::Infix(node) => self.eval_infix(node),
-            Expression::Index(_) => todo!(),
+            Expression::Index(node) => self.eval_index(node),
+            Expression::Field(node) => self.eval_field(node),
             Expression::Call(node) => self.eval_call(node),
-            Expression::List(_) => todo!(),
-            Expression::None => todo!(),
+            Expression::List(node) => self.eval_list(node),
+            Expression::None => Object::None,
             Expression::If(node) => self.eval_if(node),
-            Expression::Loop(_) => todo!(),
-            Expression::When(_) => todo!(),
+            Expression::Loop(node) => self.eval_loop(node),
+            Expression::When(node) => self.eval_when(node),
             Expression::Func(node) => self.eval_func(node),
-            Expression::Annotation(_) => todo!(),
-            Expression::Struct(_) => todo!(),
-            Expression::Enum(_) => todo!(),
+            // `@` is rejected by the parser until annotations are supported
+            Expression::Annotation(_) => unreachable!("Annotations can not be parsed"),
+            // Containers are named after the binding they are declared with
+            Expression::Struct(node) => Self::eval_struct(node, "container".into()),
+            Expression::Instance(node) => self.eval_instance(node),
+            Expression::Enum(node) => Self::eval_enum(node),
+        }
+    }
+
+    fn eval_interpolated(&mut self, node: InterpolatedExpr) -> Object {
+        let mut buf = String::new();
+        for part in node.parts {
+            match part {
+                StrPart::Lit(lit) => buf.push_str(&lit),
+                StrPart::Expr(expr) => buf.push_str(&unwind!(self.eval_expr(expr)).to_string()),
+            }
+        }
+        Object::Lit(Literal::Str(buf))
+    }
+
+    fn eval_list(&mut self, node: ListExpr) -> Object {
+        let list = match self.eval_args(node.list) {
+            Ok(list) => list,
+            Err(signal) => return signal,
+        };
+        Object::List(Rc::new(RefCell::new(list)))
+    }
+
+    fn eval_index(&mut self, node: IndexExpr) -> Object {
+        let list = unwind!(self.eval_expr(*node.list));
+        let index = unwind!(self.eval_expr(*node.index));
+        let (list, index) = Self::index_target(list, index);
+        let list = list.borrow();
+        list[index].clone()
+    }
+
+    /// Make sure the evaluated list and index of an
+    /// index expression are valid and the index is in bounds
+    fn index_target(list: Object, index: Object) -> (Rc<RefCell<Vec<Object>>>, usize) {
+        let list = match list {
+            Object::List(list) => list,
+            other => panic!("Cannot index into {}, it is not a list", other),
+        };
+        let index = match index {
+            Object::Lit(Literal::Int(int)) => int,
+            other => panic!("List indices need to be integers, got: {}", other),
+        };
+        let len = list.borrow().len();
+        match usize::try_from(index) {
+            Ok(index) if index < len => (list, index),
+            _ => panic!(
+                "Index {} is out of bounds for a list of length {}",
+                index, len
+            ),
+        }
+    }
+
+    /// Compound assignments like `+=` combine the
+    /// current value of the target with the value
+    fn eval_assign(&mut self, op: InfixOp, target: Expression, val: Expression) -> Object {
+        match target {
+            Expression::Ident(ident) => {
+                let val = unwind!(self.eval_expr(val));
+                let val = match op.compound_op() {
+                    Some(op) => Self::eval_compound(op, self.eval_ident(ident.clone()), val),
+                    None => val,
+                };
+                match self.env.borrow_mut().assign(&ident.0, val.clone()) {
+                    Ok(()) => (),
+                    Err(AssignError::Const) => {
+                        panic!("Cannot assign to the constant {}", ident)
+                    }
+                    Err(AssignError::Undefined) => panic!("Could not find identifier: {}", ident),
+                }
+                val
+            }
+            Expression::Index(node) => {
+                let list = unwind!(self.eval_expr(*node.list));
+                let index = unwind!(self.eval_expr(*node.index));
+                let (list, index) = Self::index_target(list, index);
+                let val = unwind!(self.eval_expr(val));
+                let val = match op.compound_op() {
+                    Some(op) => {
+                        let cur = list.borrow()[index].clone();
+                        Self::eval_compound(op, cur, val)
+                    }
+                    None => val,
+                };
+                list.borrow_mut()[index] = val.clone();
+                val
+            }
+            Expression::Field(node) => match unwind!(self.eval_expr(*node.val)) {
+                Object::Instance(instance) => {
+                    let pos = Self::field_pos(&instance.parent, &node.field);
+                    let val = unwind!(self.eval_expr(val));
+                    let val = match op.compound_op() {
+                        Some(op) => {
+                            let cur = instance.vals.borrow()[pos].clone();
+                            Self::eval_compound(op, cur, val)
+                        }
+                        None => val,
+                    };
+                    self.check_field_type(&instance.parent, pos, &val);
+                    instance.vals.borrow_mut()[pos] = val.clone();
+                    val
+                }
+                Object::Use(module) => {
+                    panic!(
+                        "Cannot assign to {} of the module {}",
+                        node.field, module.path
+                    )
+                }
+                Object::Enum(_) => panic!("Cannot assign to the enum variant {}", node.field),
+                other => panic!("{} has no field called {}", other, node.field),
+            },
+            other => panic!("Cannot assign to {}", other),
+        }
+    }
+
+    fn eval_compound(op: InfixOp, cur: Object, val: Object) -> Object {
+        match (
+            Self::conv_to_num(cur.clone()),
+            Self::conv_to_num(val.clone()),
+        ) {
+            (Some(cur), Some(val)) => Object::Lit(Self::eval_arithmetic(op, cur, val)),
+            _ => panic!("Cannot use {}= on {} and {}", op, cur, val),
         }
     }
 
     fn eval_var(&mut self, node: VarStmt) -> Object {
-        let val = self.eval_expr(node.val);
+        let val = match node.val {
+            Expression::Struct(_struct) => Self::eval_struct(_struct, node.name.ident.0.clone()),
+            val => self.eval_expr(val),
+        };
+        if val.is_signal() {
+            return val;
+        }
         self.env.borrow_mut().set(
             node.name.ident.0.clone(),
-            EnvObj {
-                obj: val.clone(),
-                is_const: node.is_const,
-            },
+            EnvObj::new(val.clone(), node.is_const),
         );
         val
     }
@@ -82,82 +365,540 @@ impl Evaluator {
         Object::Func(FuncObj {
             args: node.args,
             block: node.block,
+            env: Rc::clone(&self.env),
         })
     }
 
     fn eval_call(&mut self, node: CallExpr) -> Object {
-        let name = match *node.ident {
-            Expression::Ident(ident) => ident.0.clone(),
-            _ => panic!("The function name is not an identifier"),
+        if let Expression::Ident(ident) = node.ident.as_ref() {
+            match ident.0.as_str() {
+                "print" => {
+                    return match self.eval_args(node.args) {
+                        Ok(args) => Object::BuiltinFunc(BuiltinFunc::Print(Print::new(&args))),
+                        Err(signal) => signal,
+                    }
+                }
+                "input" => return Object::BuiltinFunc(BuiltinFunc::Input(Input::new(None))),
+                _ => (),
+            }
+        }
+
+        let callee = node.ident.clone();
+        let func = match Self::get_func(unwind!(self.eval_expr(*node.ident))) {
+            Some(func) => func,
+            None => panic!("Failed to find a function with the name {}", callee),
         };
+        let call_args = match self.eval_args(node.args) {
+            Ok(args) => args,
+            Err(signal) => return signal,
+        };
+        if func.args.len() != call_args.len() {
+            panic!("Amount of expected args: {}, does not match amount of provided args: {} for function: {}", func.args.len(), call_args.len(), callee)
+        }
 
-        match name.as_str() {
-            "print" => {
-                Object::BuiltinFunc(BuiltinFunc::Print(Print::new(&self.eval_args(node.args))))
+        // Arguments are bound in a scope of the function's own
+        // scope, so it can use the bindings of the file it is from
+        let mut scope = Environment::new_enclosed(Rc::clone(&func.env));
+        for (arg, call_arg) in func.args.into_iter().zip(call_args) {
+            scope.set(arg.ident.0, EnvObj::new(call_arg, false));
+        }
+        let caller = replace(&mut self.env, Rc::new(RefCell::new(scope)));
+        let last = self.eval_block(func.block);
+        self.env = caller;
+
+        match last {
+            Some(Object::Ret(obj)) => *obj,
+            Some(Object::Br(..) | Object::Cont(_)) => {
+                panic!("Cannot break out of the function {}", callee)
             }
-            "input" => Object::BuiltinFunc(BuiltinFunc::Input(Input::new(None))),
-            _ => {
-                let old_env = Rc::clone(&self.env);
+            Some(obj) => obj,
+            None => Object::Void,
+        }
+    }
+
+    fn eval_if(&mut self, node: IfExpr) -> Object {
+        let cond = match node.cond {
+            Some(cond) => match unwind!(self.eval_expr(*cond)) {
+                Object::Lit(Literal::Bool(bool)) => bool,
+                other => {
+                    panic!("The condition of an if expression needs to be a boolean, got: {other}")
+                }
+            },
+            // Else branches have no condition
+            None => true,
+        };
+
+        if cond {
+            return self.eval_block(node.block).unwrap_or(Object::Void);
+        }
 
-                let mut call_args = Vec::new();
+        match node.alt {
+            Some(alt) => self.eval_if(*alt),
+            None => Object::Void,
+        }
+    }
 
-                let call_arg_len = node.args.len();
+    /// Evaluates the result of the first case whose pattern matches
+    /// the compared value or, without one, whose condition is true.
+    /// Bindings of the pattern are only visible in the result
+    fn eval_when(&mut self, node: WhenExpr) -> Object {
+        let comp_val = match node.comp_val {
+            Some(val) => Some(unwind!(self.eval_expr(*val))),
+            None => None,
+        };
 
-                for arg in node.args {
-                    call_args.push(self.eval_expr(arg));
+        for case in node.cases {
+            let mut bindings = Vec::new();
+            let matches = match (case.pattern, case.cond, &comp_val) {
+                (Some(pattern), _, Some(comp_val)) => {
+                    self.match_pattern(&pattern, comp_val, &mut bindings)
                 }
+                (_, Some(cond), _) => match unwind!(self.eval_expr(*cond)) {
+                    Object::Lit(Literal::Bool(bool)) => bool,
+                    other => {
+                        panic!("The condition of a when case needs to be a boolean, got: {other}")
+                    }
+                },
+                _ => true,
+            };
+            if !matches {
+                continue;
+            }
 
-                // Get the function and add arguments to self.env
-                let func = {
-                    let mut env = self.env.borrow_mut();
+            let outer = self.enter_scope(bindings);
+            let val = match case.result {
+                CaseResult::Block(block) => self.eval_block(block).unwrap_or(Object::Void),
+                CaseResult::Expr(expr) => self.eval_expr(*expr),
+            };
+            self.env = outer;
+            return val;
+        }
+        Object::Void
+    }
 
-                    let func_obj = env.get(&name).unwrap_or_else(|| {
-                        panic!("Failed to find a function with the name {}", &name)
-                    });
+    /// Runs the alternative of the loop if its block was never evaluated.
+    /// Evaluates to the value of the break statement that ended the loop
+    fn eval_loop(&mut self, node: LoopExpr) -> Object {
+        let val = match node._type {
+            LoopType::While | LoopType::ElseWhile => {
+                self.eval_while(node.cond, &node.label, &node.block)
+            }
+            LoopType::For | LoopType::ElseFor => match node.cond {
+                Some(cond) => self.eval_for(*cond, &node.label, &node.block),
+                None => panic!("For loops need an iterator"),
+            },
+            LoopType::Else => {
+                let outer = self.enter_scope(Vec::new());
+                let val = self.eval_block(node.block);
+                self.env = outer;
+                Some(val.unwrap_or(Object::Void))
+            }
+        };
 
-                    let func = Self::get_func(func_obj.obj.clone()).unwrap_or_else(|| {
-                        panic!("Failed to find a function with the name {}", &name)
-                    });
+        match (val, node.alt) {
+            (Some(val), _) => val,
+            (None, Some(alt)) => self.eval_loop(*alt),
+            (None, None) => Object::Void,
+        }
+    }
 
-                    if func.args.len() != call_arg_len {
-                        panic!("Amount of expected args: {}, does not match amount of provided args: {} for function: {}", func.args.len(), call_arg_len, name)
-                    }
+    /// Loops without a condition run until they are broken out of.
+    /// Returns None if the block was never evaluated
+    fn eval_while(
+        &mut self,
+        cond: Option<Box<Expression>>,
+        label: &Option<Ident>,
+        block: &BlockStmt,
+    ) -> Option<Object> {
+        let mut ran = false;
+        loop {
+            if let Some(cond) = &cond {
+                match self.eval_expr(*cond.clone()) {
+                    Object::Lit(Literal::Bool(true)) => (),
+                    Object::Lit(Literal::Bool(false)) => break,
+                    // Signals in the condition target a surrounding loop or function
+                    signal if signal.is_signal() => return Some(signal),
+                    other => panic!("The condition of a loop needs to be a boolean, got: {other}"),
+                }
+            }
+            ran = true;
+            if let Some(val) = self.eval_iteration(label, block, Vec::new()) {
+                return Some(val);
+            }
+        }
+        ran.then_some(Object::Void)
+    }
 
-                    for (arg, call_arg) in func.args.clone().into_iter().zip(call_args) {
-                        env.set(
-                            arg.ident.0,
-                            EnvObj::new(call_arg, false),
-                        );
-                    }
+    /// Iterates over ranges and lists.
+    /// Returns None if the block was never evaluated
+    fn eval_for(
+        &mut self,
+        cond: Expression,
+        label: &Option<Ident>,
+        block: &BlockStmt,
+    ) -> Option<Object> {
+        let (ident, iter) = match cond {
+            Expression::Infix(InfixExpr {
+                op: InfixOp::In,
+                left,
+                right,
+            }) => match *left {
+                Expression::Ident(ident) => (ident, *right),
+                other => panic!("Expected an identifier to loop with, got: {}", other),
+            },
+            other => panic!("Expected `ident in iterator`, got: {}", other),
+        };
+        let items: Box<dyn Iterator<Item = Object>> = match self.eval_expr(iter) {
+            Object::Range(range) => {
+                Box::new((range.start..range.end).map(|int| Object::Lit(Literal::Int(int))))
+            }
+            // Changes to the list while looping do not affect the loop
+            Object::List(list) => Box::new(list.borrow().clone().into_iter()),
+            signal if signal.is_signal() => return Some(signal),
+            other => panic!("Cannot loop over {}", other),
+        };
 
-                    func
-                };
+        let mut ran = false;
+        for item in items {
+            ran = true;
+            if let Some(val) = self.eval_iteration(label, block, vec![(ident.0.clone(), item)]) {
+                return Some(val);
+            }
+        }
+        ran.then_some(Object::Void)
+    }
 
-                let last = self.eval_block(func.block);
+    /// Evaluates the block of a loop once. Returns the value the loop
+    /// evaluates to if it has to stop, which is either the value of a
+    /// break statement or a signal for a surrounding loop or function
+    fn eval_iteration(
+        &mut self,
+        label: &Option<Ident>,
+        block: &BlockStmt,
+        bindings: Vec<(String, Object)>,
+    ) -> Option<Object> {
+        let outer = self.enter_scope(bindings);
+        let val = self.eval_block(block.clone());
+        self.env = outer;
 
-                self.env = old_env;
-                match last {
-                    Some(obj) => obj,
-                    None => Object::Void,
+        let targets_loop = |target: &Option<Ident>| target.is_none() || target == label;
+        match val? {
+            Object::Br(target, val) if targets_loop(&target) => Some(*val),
+            Object::Cont(target) if targets_loop(&target) => None,
+            signal @ (Object::Br(..) | Object::Cont(_) | Object::Ret(_)) => Some(signal),
+            _ => None,
+        }
+    }
+
+    /// Creates a new scope with the bindings and
+    /// returns the scope that needs to be restored
+    fn enter_scope(&mut self, bindings: Vec<(String, Object)>) -> Rc<RefCell<Environment>> {
+        let mut scope = Environment::new_enclosed(Rc::clone(&self.env));
+        for (name, obj) in bindings {
+            scope.set(name, EnvObj::new(obj, false));
+        }
+        replace(&mut self.env, Rc::new(RefCell::new(scope)))
+    }
+
+    /// Checks if the value matches the pattern and collects the
+    /// values that are bound by it. Bindings of patterns that
+    /// do not match should be discarded
+    fn match_pattern(
+        &mut self,
+        pattern: &Pattern,
+        val: &Object,
+        bindings: &mut Vec<(String, Object)>,
+    ) -> bool {
+        match pattern {
+            Pattern::Value(expr) => {
+                let expected = self.eval_expr(expr.clone());
+                Self::objects_eq(val.clone(), expected)
+            }
+            Pattern::Range(range) => self.match_range(range, val),
+            Pattern::None => matches!(val, Object::None),
+            Pattern::Binding(ident) => {
+                bindings.push((ident.0.clone(), val.clone()));
+                true
+            }
+            Pattern::Wildcard => true,
+            Pattern::List(list) => self.match_list(list, val, bindings),
+            Pattern::Struct(_struct) => self.match_struct(_struct, val, bindings),
+            Pattern::Variant(variant) => self.match_variant(variant, val),
+        }
+    }
+
+    fn match_range(&mut self, range: &RangePattern, val: &Object) -> bool {
+        if Self::conv_to_num(val.clone()).is_none() {
+            return false;
+        }
+        let start = self.eval_expr(*range.start.clone());
+        let end = self.eval_expr(*range.end.clone());
+        let (start, val_comp) = Self::to_comp(start, val.clone());
+        let (end, val) = Self::to_comp(end, val.clone());
+        start <= val_comp && val < end
+    }
+
+    fn match_list(
+        &mut self,
+        pattern: &ListPattern,
+        val: &Object,
+        bindings: &mut Vec<(String, Object)>,
+    ) -> bool {
+        let list = match val {
+            Object::List(list) => list.borrow().clone(),
+            _ => return false,
+        };
+        let (head, tail) = (pattern.head.len(), pattern.tail.len());
+        let len_matches = match pattern.rest {
+            Some(_) => list.len() >= head + tail,
+            None => list.len() == head + tail,
+        };
+        if !len_matches {
+            return false;
+        }
+
+        let tail_start = list.len() - tail;
+        let items = pattern.head.iter().zip(&list[..head]);
+        let tail_items = pattern.tail.iter().zip(&list[tail_start..]);
+        for (pattern, item) in items.chain(tail_items) {
+            if !self.match_pattern(pattern, item, bindings) {
+                return false;
+            }
+        }
+
+        if let Some(binding) = pattern.rest.as_ref().and_then(|rest| rest.binding.as_ref()) {
+            let rest = list[head..tail_start].to_vec();
+            bindings.push((binding.0.clone(), Object::List(Rc::new(RefCell::new(rest)))));
+        }
+        true
+    }
+
+    fn match_struct(
+        &mut self,
+        pattern: &StructPattern,
+        val: &Object,
+        bindings: &mut Vec<(String, Object)>,
+    ) -> bool {
+        let parent = self.get_struct(&pattern.name);
+        let instance = match val {
+            Object::Instance(instance) if Rc::ptr_eq(&instance.parent, &parent) => instance,
+            _ => return false,
+        };
+        pattern.fields.iter().all(|field| {
+            let pos = Self::field_pos(&parent, &field.field);
+            let val = instance.vals.borrow()[pos].clone();
+            self.match_pattern(&field.pattern, &val, bindings)
+        })
+    }
+
+    fn match_variant(&mut self, pattern: &VariantPattern, val: &Object) -> bool {
+        let expected = self.eval_variant(pattern.parent.clone(), &pattern.variant);
+        match val {
+            Object::Variant(variant) => {
+                Rc::ptr_eq(&variant.parent, &expected.parent) && variant.name == expected.name
+            }
+            _ => false,
+        }
+    }
+
+    fn eval_enum(node: EnumExpr) -> Object {
+        let variants = node.variants.into_iter().map(|variant| variant.0).collect();
+        Object::Enum(Rc::new(EnumObj { variants }))
+    }
+
+    fn eval_struct(node: StructExpr, name: String) -> Object {
+        Object::Struct(Rc::new(StructObj {
+            name,
+            fields: node.fields,
+        }))
+    }
+
+    /// Every field of the container needs to be set exactly once
+    fn eval_instance(&mut self, node: InstanceExpr) -> Object {
+        let parent = self.get_struct(&node.name);
+        let mut vals = vec![None; parent.fields.len()];
+        for init in node.fields {
+            let pos = Self::field_pos(&parent, &init.field);
+            if vals[pos].is_some() {
+                panic!("The field {} of {} is set twice", init.field, parent.name);
+            }
+            let val = unwind!(self.eval_expr(init.val));
+            self.check_field_type(&parent, pos, &val);
+            vals[pos] = Some(val);
+        }
+
+        let vals = vals
+            .into_iter()
+            .zip(&parent.fields)
+            .map(|(val, field)| {
+                val.unwrap_or_else(|| panic!("Missing field {} of {}", field.ident, parent.name))
+            })
+            .collect();
+        Object::Instance(InstanceObj {
+            parent,
+            vals: Rc::new(RefCell::new(vals)),
+        })
+    }
+
+    fn get_struct(&mut self, name: &Ident) -> Rc<StructObj> {
+        match self.eval_ident(name.clone()) {
+            Object::Struct(_struct) => _struct,
+            _ => panic!("{} is not a container", name),
+        }
+    }
+
+    fn field_pos(parent: &StructObj, field: &Ident) -> usize {
+        parent
+            .field_pos(field)
+            .unwrap_or_else(|| panic!("{} has no field called {}", parent.name, field))
+    }
+
+    fn check_field_type(&self, parent: &StructObj, pos: usize, val: &Object) {
+        let field = &parent.fields[pos];
+        if let Some(_type) = &field._type {
+            if !self.is_type(_type, val) {
+                panic!(
+                    "The field {} of {} needs to be {}, got: {}",
+                    field.ident, parent.name, _type, val
+                );
+            }
+        }
+    }
+
+    /// Checks if the value has the builtin type, or is an
+    /// instance or variant of the container or enum with the name
+    fn is_type(&self, _type: &Ident, val: &Object) -> bool {
+        match (_type.0.as_str(), val) {
+            ("Str", Object::Lit(Literal::Str(_)))
+            | ("Int", Object::Lit(Literal::Int(_)))
+            // Integers are valid numbers
+            | ("Num", Object::Lit(Literal::Num(_) | Literal::Int(_)))
+            | ("Bool", Object::Lit(Literal::Bool(_)))
+            | ("List", Object::List(_))
+            | ("Range", Object::Range(_))
+            | ("Func", Object::Func(_)) => true,
+            ("Str" | "Int" | "Num" | "Bool" | "List" | "Range" | "Func", _) => false,
+            _ => match self.env.borrow().get(&_type.0).map(|obj| obj.obj) {
+                Some(Object::Struct(_struct)) => matches!(
+                    val,
+                    Object::Instance(instance) if Rc::ptr_eq(&instance.parent, &_struct)
+                ),
+                Some(Object::Enum(_enum)) => matches!(
+                    val,
+                    Object::Variant(variant) if Rc::ptr_eq(&variant.parent, &_enum)
+                ),
+                _ => panic!("Unknown type: {}", _type),
+            },
+        }
+    }
+
+    fn eval_field(&mut self, node: FieldExpr) -> Object {
+        match unwind!(self.eval_expr(*node.val)) {
+            Object::Instance(instance) => {
+                let pos = Self::field_pos(&instance.parent, &node.field);
+                let val = instance.vals.borrow()[pos].clone();
+                val
+            }
+            Object::Enum(_enum) => Object::Variant(Self::get_variant(_enum, &node.field)),
+            Object::Use(module) => Self::get_export(&module, &node.field),
+            other => panic!("{} has no field called {}", other, node.field),
+        }
+    }
+
+    fn eval_variant(&mut self, parent: Ident, variant: &Ident) -> VariantObj {
+        match self.eval_ident(parent.clone()) {
+            Object::Enum(_enum) => Self::get_variant(_enum, variant),
+            _ => panic!("{} is not an enum", parent),
+        }
+    }
+
+    fn get_variant(_enum: Rc<EnumObj>, variant: &Ident) -> VariantObj {
+        if !_enum.variants.contains(&variant.0) {
+            panic!("The enum has no variant called {}", variant)
+        }
+        VariantObj {
+            parent: _enum,
+            name: variant.0.clone(),
+        }
+    }
+
+    /// Checks if a range contains a number, a list contains
+    /// a value or a string contains another string
+    fn eval_contains(val: Object, container: Object) -> bool {
+        match container {
+            Object::Range(range) => match val {
+                Object::Lit(Literal::Int(int)) => range.start <= int && int < range.end,
+                Object::Lit(Literal::Num(num)) => {
+                    range.start as f64 <= num && num < range.end as f64
                 }
+                _ => false,
+            },
+            Object::List(list) => list
+                .borrow()
+                .iter()
+                .any(|item| Self::objects_eq(val.clone(), item.clone())),
+            Object::Lit(Literal::Str(str)) => match val {
+                Object::Lit(Literal::Str(val)) => str.contains(&val),
+                other => panic!("Cannot check if a string contains {}", other),
+            },
+            other => panic!("Cannot check if {} contains a value", other),
+        }
+    }
+
+    /// Values that cannot be compared are never equal
+    fn objects_eq(left: Object, right: Object) -> bool {
+        match (&left, &right) {
+            (Object::Lit(_) | Object::None, Object::Lit(_) | Object::None) => {
+                let (left, right) = Self::to_comp(left, right);
+                left == right
+            }
+            (Object::Variant(left), Object::Variant(right)) => {
+                Rc::ptr_eq(&left.parent, &right.parent) && left.name == right.name
             }
+            (Object::List(left), Object::List(right)) => {
+                let items = left.borrow().clone();
+                let right = right.borrow();
+                items.len() == right.len()
+                    && items
+                        .into_iter()
+                        .zip(right.iter())
+                        .all(|(left, right)| Self::objects_eq(left, right.clone()))
+            }
+            (Object::Instance(left), Object::Instance(right)) => {
+                let vals = left.vals.borrow().clone();
+                Rc::ptr_eq(&left.parent, &right.parent)
+                    && vals
+                        .into_iter()
+                        .zip(right.vals.borrow().iter())
+                        .all(|(left, right)| Self::objects_eq(left, right.clone()))
+            }
+            _ => false,
         }
     }
 
-    fn eval_args(&mut self, args: Vec<Expression>) -> Vec<Object> {
-        args.into_iter().map(|arg| self.eval_expr(arg)).collect()
+    /// Stops at the first argument that evaluates
+    /// to a signal and returns it as the error
+    fn eval_args(&mut self, args: Vec<Expression>) -> Result<Vec<Object>, Object> {
+        args.into_iter()
+            .map(|arg| match self.eval_expr(arg) {
+                signal if signal.is_signal() => Err(signal),
+                val => Ok(val),
+            })
+            .collect()
     }
 
+    /// Returns the value of the last statement. Statements
+    /// after a break, continue or return are skipped
     fn eval_block(&mut self, block: BlockStmt) -> Option<Object> {
-        let len = block.stmts.len();
-
-        for (i, stmt) in block.stmts.into_iter().enumerate() {
-            if i == len {
-                return Some(self.eval_stmt(stmt));
+        let mut last = None;
+        for stmt in block.stmts {
+            let val = self.eval_stmt(stmt);
+            if val.is_signal() {
+                return Some(val);
             }
-            self.eval_stmt(stmt);
+            last = Some(val);
         }
-        None
+        last
     }
 
     fn eval_ident(&mut self, node: Ident) -> Object {
@@ -171,17 +912,22 @@ impl Evaluator {
         match &node.op {
             PrefixOp::Pos => self.eval_expr(*node.val),
             PrefixOp::Neg => {
-                let val = self.eval_expr(*node.val);
-                Object::Lit(Literal::Num(match val {
+                let val = unwind!(self.eval_expr(*node.val));
+                Object::Lit(match val {
                     Object::Lit(lit) => match lit {
-                        Literal::Num(num) => -num,
+                        Literal::Int(int) => {
+                            Literal::Int(int.checked_neg().unwrap_or_else(|| {
+                                panic!("Integer overflow while negating {}", int)
+                            }))
+                        }
+                        Literal::Num(num) => Literal::Num(-num),
                         _ => panic!("The obj is not a number"),
                     },
                     _ => panic!("The obj does not evaluate to a literal"),
-                }))
+                })
             }
             PrefixOp::Not => {
-                let val = self.eval_expr(*node.val);
+                let val = unwind!(self.eval_expr(*node.val));
                 Object::Lit(Literal::Bool(match val {
                     Object::Lit(lit) => match lit {
                         Literal::Bool(bool) => !bool,
@@ -199,56 +945,136 @@ impl Evaluator {
 
     fn eval_infix_from_num(&mut self, op: InfixOp, left: Expression, right: Expression) -> Object {
         match op {
-            InfixOp::Add => {
-                let (left, right) = self.eval_infix_to_num(left, right);
-                Object::Lit(Literal::Num(left + right))
-            }
-            InfixOp::Sub => {
-                let (left, right) = self.eval_infix_to_num(left, right);
-                Object::Lit(Literal::Num(left - right))
-            }
-            InfixOp::Mul => {
-                let (left, right) = self.eval_infix_to_num(left, right);
-                Object::Lit(Literal::Num(left * right))
-            }
-            InfixOp::Div => {
-                let (left, right) = self.eval_infix_to_num(left, right);
-                Object::Lit(Literal::Num(left / right))
-            }
-            InfixOp::Eq => {
-                let (left, right) = self.eval_infix_to_comp(left, right);
-                Object::Lit(Literal::Bool(left == right))
-            }
-            InfixOp::NEq => {
-                let (left, right) = self.eval_infix_to_comp(left, right);
-                Object::Lit(Literal::Bool(left != right))
+            InfixOp::And | InfixOp::Or => return self.eval_logical(op, left, right),
+            InfixOp::As => return self.eval_conversion(left, right),
+            InfixOp::Assign
+            | InfixOp::AddAssign
+            | InfixOp::SubAssign
+            | InfixOp::MulAssign
+            | InfixOp::DivAssign
+            | InfixOp::ModAssign => return self.eval_assign(op, left, right),
+            _ => (),
+        }
+
+        let left = unwind!(self.eval_expr(left));
+        let right = unwind!(self.eval_expr(right));
+        match op {
+            InfixOp::Add | InfixOp::Sub | InfixOp::Mul | InfixOp::Div | InfixOp::Mod => {
+                let (left, right) = Self::to_nums(left, right);
+                Object::Lit(Self::eval_arithmetic(op, left, right))
             }
+            InfixOp::Eq => Object::Lit(Literal::Bool(Self::objects_eq(left, right))),
+            InfixOp::NEq => Object::Lit(Literal::Bool(!Self::objects_eq(left, right))),
             InfixOp::GT => {
-                let (left, right) = self.eval_infix_to_comp(left, right);
+                let (left, right) = Self::to_comp(left, right);
                 Object::Lit(Literal::Bool(left > right))
             }
             InfixOp::LT => {
-                let (left, right) = self.eval_infix_to_comp(left, right);
+                let (left, right) = Self::to_comp(left, right);
                 Object::Lit(Literal::Bool(left < right))
             }
             InfixOp::GTEq => {
-                let (left, right) = self.eval_infix_to_comp(left, right);
+                let (left, right) = Self::to_comp(left, right);
                 Object::Lit(Literal::Bool(left >= right))
             }
             InfixOp::LTEq => {
-                let (left, right) = self.eval_infix_to_comp(left, right);
+                let (left, right) = Self::to_comp(left, right);
                 Object::Lit(Literal::Bool(left <= right))
             }
-            InfixOp::As => todo!(),
-            InfixOp::In => todo!(),
-            InfixOp::Range => todo!(),
-            InfixOp::Assign => todo!(),
+            InfixOp::In => Object::Lit(Literal::Bool(Self::eval_contains(left, right))),
+            InfixOp::Range => match (left, right) {
+                (Object::Lit(Literal::Int(start)), Object::Lit(Literal::Int(end))) => {
+                    Object::Range(RangeObj { start, end })
+                }
+                (start, end) => panic!(
+                    "The bounds of a range need to be integers, got: {}..{}",
+                    start, end
+                ),
+            },
+            other => unreachable!("{} is evaluated without its operands", other),
+        }
+    }
+
+    /// The right operand is the name of the type to convert to.
+    /// Numbers and strings can be converted into each other
+    fn eval_conversion(&mut self, val: Expression, _type: Expression) -> Object {
+        let _type = match _type {
+            Expression::Ident(_type) => _type,
+            other => panic!("Expected a type to convert to, got: {}", other),
+        };
+        let val = unwind!(self.eval_expr(val));
+        let lit = match (_type.0.as_str(), &val) {
+            ("Int", Object::Lit(Literal::Int(int))) => Some(Literal::Int(*int)),
+            ("Int", Object::Lit(Literal::Num(num))) => Some(Literal::Int(num.trunc() as i64)),
+            ("Int", Object::Lit(Literal::Str(str))) => str.trim().parse().ok().map(Literal::Int),
+            ("Num", Object::Lit(Literal::Int(int))) => Some(Literal::Num(*int as f64)),
+            ("Num", Object::Lit(Literal::Num(num))) => Some(Literal::Num(*num)),
+            ("Num", Object::Lit(Literal::Str(str))) => str.trim().parse().ok().map(Literal::Num),
+            ("Str", Object::Lit(_)) => Some(Literal::Str(val.to_string())),
+            _ => None,
+        };
+        match lit {
+            Some(lit) => Object::Lit(lit),
+            None => panic!("Cannot convert {} to {}", val, _type),
+        }
+    }
+
+    /// The right operand is only evaluated if the left
+    /// one does not already decide the result
+    fn eval_logical(&mut self, op: InfixOp, left: Expression, right: Expression) -> Object {
+        let to_bool = |val: Object| match val {
+            Object::Lit(Literal::Bool(bool)) => bool,
+            other => panic!("The operands of `{op}` need to be booleans, got: {other}"),
+        };
+        let left = to_bool(unwind!(self.eval_expr(left)));
+        let decided = match op {
+            InfixOp::And => !left,
+            InfixOp::Or => left,
+            _ => unreachable!(),
+        };
+        if decided {
+            return Object::Lit(Literal::Bool(left));
+        }
+        Object::Lit(Literal::Bool(to_bool(unwind!(self.eval_expr(right)))))
+    }
+
+    /// Integers stay integers unless one of the operands is a float
+    fn eval_arithmetic(op: InfixOp, left: Literal, right: Literal) -> Literal {
+        match (left, right) {
+            (Literal::Int(left), Literal::Int(right)) => {
+                if matches!(op, InfixOp::Div | InfixOp::Mod) && right == 0 {
+                    panic!("Cannot divide {} by zero", left)
+                }
+                let res = match op {
+                    InfixOp::Add => left.checked_add(right),
+                    InfixOp::Sub => left.checked_sub(right),
+                    InfixOp::Mul => left.checked_mul(right),
+                    InfixOp::Div => left.checked_div(right),
+                    InfixOp::Mod => left.checked_rem(right),
+                    other => panic!("{} is not an arithmetic operator", other),
+                };
+                Literal::Int(res.unwrap_or_else(|| {
+                    panic!(
+                        "Integer overflow while evaluating {} {} {}",
+                        left, op, right
+                    )
+                }))
+            }
+            (left, right) => {
+                let (left, right) = (Self::conv_to_float(left), Self::conv_to_float(right));
+                Literal::Num(match op {
+                    InfixOp::Add => left + right,
+                    InfixOp::Sub => left - right,
+                    InfixOp::Mul => left * right,
+                    InfixOp::Div => left / right,
+                    InfixOp::Mod => left % right,
+                    other => panic!("{} is not an arithmetic operator", other),
+                })
+            }
         }
     }
 
-    fn eval_infix_to_num(&mut self, left: Expression, right: Expression) -> (f64, f64) {
-        let left = self.eval_expr(left);
-        let right = self.eval_expr(right);
+    fn to_nums(left: Object, right: Object) -> (Literal, Literal) {
         (
             match Self::conv_to_num(left) {
                 Some(val) => val,
@@ -261,19 +1087,23 @@ impl Evaluator {
         )
     }
 
-    fn eval_infix_to_comp(
-        &mut self,
-        left: Expression,
-        right: Expression,
-    ) -> (Comparable, Comparable) {
-        let left = self.eval_expr(left);
-        let right = self.eval_expr(right);
+    fn to_comp(left: Object, right: Object) -> (Comparable, Comparable) {
+        let (left, right) = match (left, right) {
+            // Compare integers with floats as floats
+            (Object::Lit(Literal::Int(int)), right @ Object::Lit(Literal::Num(_))) => {
+                (Object::Lit(Literal::Num(int as f64)), right)
+            }
+            (left @ Object::Lit(Literal::Num(_)), Object::Lit(Literal::Int(int))) => {
+                (left, Object::Lit(Literal::Num(int as f64)))
+            }
+            other => other,
+        };
         (
             match left {
                 Object::Lit(lit) => Comparable::Lit(lit),
                 Object::None => Comparable::None,
                 _ => panic!(
-                    "Cannot compare left: {:#?} since it is not valid for comparison",
+                    "Cannot compare left: {} since it is not valid for comparison",
                     &left
                 ),
             },
@@ -281,20 +1111,28 @@ impl Evaluator {
                 Object::Lit(lit) => Comparable::Lit(lit),
                 Object::None => Comparable::None,
                 _ => panic!(
-                    "Cannot compare right: {:#?} since it is not valid for comparison",
+                    "Cannot compare right: {} since it is not valid for comparison",
                     &right
                 ),
             },
         )
     }
 
-    fn conv_to_num(obj: Object) -> Option<f64> {
+    fn conv_to_num(obj: Object) -> Option<Literal> {
         match obj {
-            Object::Lit(Literal::Num(num)) => Some(num),
+            Object::Lit(lit @ (Literal::Int(_) | Literal::Num(_))) => Some(lit),
             _ => None,
         }
     }
 
+    fn conv_to_float(lit: Literal) -> f64 {
+        match lit {
+            Literal::Int(int) => int as f64,
+            Literal::Num(num) => num,
+            other => panic!("{} is not a number", other),
+        }
+    }
+
     fn get_func(obj: Object) -> Option<FuncObj> {
         match obj {
             Object::Func(func) => Some(func),
@@ -1,8 +1,11 @@
-use std::fmt::Display;
+use std::{cell::RefCell, fmt::Display, rc::Rc};
 
 use crate::{
-    evaluator::builtins::BuiltinFunc,
-    parser::ast::{BlockStmt, Ident, OptionallyTypedIdent, Literal},
+    evaluator::{
+        builtins::BuiltinFunc,
+        env::{EnvObj, Environment},
+    },
+    parser::ast::{BlockStmt, Ident, Literal, OptionallyTypedIdent},
 };
 
 use crate::util;
@@ -14,24 +17,110 @@ pub enum Object {
     Void,
     // TODO: Implement error system
     Err,
-    // TOOD: Implement multi file shenanigans
-    Use,
+    // An imported module
+    Use(Rc<ModuleObj>),
     // Rc<Object> is the return value
     Ret(Box<Object>),
+    // Ident is the label and Box<Object> the value of the loop
+    Br(Option<Ident>, Box<Object>),
     // Ident is the label
-    Br(Ident),
+    Cont(Option<Ident>),
     Func(FuncObj),
     BuiltinFunc(BuiltinFunc),
-    // TODO: Implement these
-    Range,
+    Range(RangeObj),
+    // TODO: Implement this
     Type,
-    List,
+    // Lists are shared between all bindings
+    // they are assigned to
+    List(Rc<RefCell<Vec<Object>>>),
+    Enum(Rc<EnumObj>),
+    Variant(VariantObj),
+    Struct(Rc<StructObj>),
+    Instance(InstanceObj),
+}
+
+/// Integers from start up to the exclusive end
+#[derive(Debug, Clone, Copy)]
+pub struct RangeObj {
+    pub start: i64,
+    pub end: i64,
+}
+
+#[derive(Debug)]
+pub struct EnumObj {
+    pub variants: Vec<String>,
 }
 
 #[derive(Debug, Clone)]
+pub struct VariantObj {
+    /// The enum the variant belongs to
+    pub parent: Rc<EnumObj>,
+    pub name: String,
+}
+
+#[derive(Debug)]
+pub struct StructObj {
+    /// Name of the binding the container was declared with
+    pub name: String,
+    pub fields: Vec<OptionallyTypedIdent>,
+}
+
+impl StructObj {
+    /// Position of the field in the values of an instance
+    pub fn field_pos(&self, field: &Ident) -> Option<usize> {
+        self.fields.iter().position(|typed| typed.ident == *field)
+    }
+}
+
+/// Instances are shared between all bindings
+/// they are assigned to, like lists
+#[derive(Debug, Clone)]
+pub struct InstanceObj {
+    pub parent: Rc<StructObj>,
+    /// Values of the fields in the order they were declared in
+    pub vals: Rc<RefCell<Vec<Object>>>,
+}
+
+#[derive(Debug)]
+pub struct ModuleObj {
+    /// The path the module was imported with like `a.b`
+    pub path: String,
+    /// The top level scope of the module
+    pub env: Rc<RefCell<Environment>>,
+}
+
+impl ModuleObj {
+    /// Looks up a binding of the module, including local ones
+    pub fn get(&self, name: &Ident) -> Option<EnvObj> {
+        self.env.borrow().get(&name.0)
+    }
+}
+
+impl Object {
+    /// Checks if the object unwinds blocks until
+    /// it reaches a loop or a function
+    pub fn is_signal(&self) -> bool {
+        matches!(self, Object::Ret(_) | Object::Br(..) | Object::Cont(_))
+    }
+}
+
+#[derive(Clone)]
 pub struct FuncObj {
     pub args: Vec<OptionallyTypedIdent>,
     pub block: BlockStmt,
+    /// The scope the function was defined in. Calls
+    /// are evaluated in a scope enclosed by it
+    pub env: Rc<RefCell<Environment>>,
+}
+
+// The scope usually contains the function itself
+impl std::fmt::Debug for FuncObj {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FuncObj")
+            .field("args", &self.args)
+            .field("block", &self.block)
+            .finish_non_exhaustive()
+    }
 }
 
 #[derive(Debug, PartialEq, PartialOrd)]
@@ -49,9 +138,10 @@ impl Display for Object {
                 Object::Lit(lit) => lit.to_string(),
                 Object::None => "none".into(),
                 Object::Err => todo!(),
-                Object::Use => todo!(),
-                Object::Ret(_) => todo!(),
-                Object::Br(_) => todo!(),
+                Object::Use(module) => format!("module {}", module.path),
+                Object::Ret(val) => val.to_string(),
+                Object::Br(..) => "break".into(),
+                Object::Cont(_) => "continue".into(),
                 Object::Func(func) => format!(
                     "func({}) {{\n{}\n}}",
                     util::typed_vec_to_string(&func.args),
@@ -61,10 +151,39 @@ impl Display for Object {
                     Some(func) => func.to_string(),
                     None => todo!(),
                 },
-                Object::Range => todo!(),
+                Object::Range(range) => format!("{}..{}", range.start, range.end),
                 Object::Type => todo!(),
-                Object::List => todo!(),
-                Object::Void => "()".into()
+                Object::List(list) => {
+                    let items: Vec<_> = list.borrow().iter().map(|obj| obj.to_string()).collect();
+                    format!("[{}]", items.join(", "))
+                }
+                Object::Enum(_enum) => format!("enum {{ {} }}", _enum.variants.join(", ")),
+                Object::Variant(variant) => variant.name.clone(),
+                Object::Struct(_struct) => {
+                    let fields: Vec<_> = _struct
+                        .fields
+                        .iter()
+                        .map(|field| field.to_string())
+                        .collect();
+                    match fields.is_empty() {
+                        true => "container".into(),
+                        false => format!("container {{ {} }}", fields.join(", ")),
+                    }
+                }
+                Object::Instance(instance) => {
+                    let fields: Vec<_> = instance
+                        .parent
+                        .fields
+                        .iter()
+                        .zip(instance.vals.borrow().iter())
+                        .map(|(field, val)| format!("{}: {}", field.ident, val))
+                        .collect();
+                    match fields.is_empty() {
+                        true => format!("{} {{}}", instance.parent.name),
+                        false => format!("{} {{ {} }}", instance.parent.name, fields.join(", ")),
+                    }
+                }
+                Object::Void => "()".into(),
             }
         )
     }
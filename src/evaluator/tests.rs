@@ -1,17 +1,619 @@
 #[cfg(test)]
 mod tests {
     use crate::lexer::Lexer;
-    use crate::parser::Parser;
+    use crate::parser::{ast::Program, Parser};
 
-    use crate::evaluator::Evaluator;
+    use crate::evaluator::{loader::ModuleLoader, Evaluator};
+    use crate::lexer::source::MemoryProvider;
 
     #[test]
     fn test_evaluator() {
         let mut lexer = Lexer::new(&"tests/evaluator/test.nx".into()).expect("Failed to find file");
         let mut parser = Parser::new(&mut lexer);
         let mut evaluator = Evaluator::new();
-        evaluator.eval_stmt(parser.parse_stmt().expect("Encountered eol"));
-        parser.next_token();
-        evaluator.eval_stmt(parser.parse_stmt().expect("Encountered eol"));
+        let program = parser.parse_program().expect("Failed to parse");
+        evaluator.eval_program(program);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_embedded_source() {
+        let evaluator = eval("x :: 40 + 2");
+        assert_eq!("42", get_var(&evaluator, "x"));
+    }
+
+    /// Test for checking if a program evaluates
+    /// to the value of its last statement
+    #[test]
+    fn test_program_value() {
+        let mut lexer = Lexer::from_string("x :: 2\n\nx * 21\n", None);
+        let mut parser = Parser::new(&mut lexer);
+        let program = parser.parse_program().expect("Failed to parse");
+        let mut evaluator = Evaluator::new();
+        assert_eq!("42", evaluator.eval_program(program).to_string());
+    }
+
+    #[test]
+    fn test_interpolation() {
+        let evaluator = eval(
+            r#"name :: "World"
+            greeting :: "Hello, {name}! {1 + 2} {"nested {name}"}"
+            "#,
+        );
+        assert_eq!(
+            "Hello, World! 3 nested World",
+            get_var(&evaluator, "greeting")
+        );
+    }
+
+    #[test]
+    fn test_numbers() {
+        let evaluator = eval(
+            "big :: 9_007_199_254_740_993 + 0x10
+            quot :: 7 / 2
+            mixed :: 7 / 2.0
+            same :: 1 == 1.0
+            rem :: 0b1011 % 4
+            grouped :: (1 + 2) * -(3 - 5)
+            nothing :: none",
+        );
+        assert_eq!("9007199254741009", get_var(&evaluator, "big"));
+        assert_eq!("3", get_var(&evaluator, "quot"));
+        assert_eq!("3.5", get_var(&evaluator, "mixed"));
+        assert_eq!("true", get_var(&evaluator, "same"));
+        assert_eq!("3", get_var(&evaluator, "rem"));
+        assert_eq!("6", get_var(&evaluator, "grouped"));
+        assert_eq!("none", get_var(&evaluator, "nothing"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot divide 1 by zero")]
+    fn test_division_by_zero() {
+        eval("x :: 1 / 0");
+    }
+
+    #[test]
+    fn test_lists() {
+        let evaluator = eval(
+            "xs :: [1, 2, [3, 4]]
+            i :: 1
+            second :: xs[i]
+            nested :: xs[i + 1][0]
+            shared :: xs
+            shared[0] = 10
+            same :: [1, [2]] == [1, [2]]
+            different :: [1, 2] != [1, 2, 3]",
+        );
+        assert_eq!("2", get_var(&evaluator, "second"));
+        assert_eq!("3", get_var(&evaluator, "nested"));
+        assert_eq!("[10, 2, [3, 4]]", get_var(&evaluator, "xs"));
+        assert_eq!("true", get_var(&evaluator, "same"));
+        assert_eq!("true", get_var(&evaluator, "different"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot compare left: [1] since it is not valid for comparison")]
+    fn test_compare_lists() {
+        eval("[1] < [2]");
+    }
+
+    #[test]
+    #[should_panic(expected = "Index 3 is out of bounds for a list of length 3")]
+    fn test_index_out_of_bounds() {
+        eval("xs :: [1, 2, 3]\nxs[3]");
+    }
+
+    #[test]
+    #[should_panic(expected = "Index -1 is out of bounds for a list of length 1")]
+    fn test_negative_index() {
+        eval("xs :: [1]\nxs[-1] = 2");
+    }
+
+    #[test]
+    fn test_when() {
+        let evaluator = eval(
+            "x :: 2
+            subject :: when x * 2 {
+                2 -> \"two\"
+                4 -> {
+                    y :: x + 1
+                    y * 10
+                }
+                else -> \"other\"
+            }
+            fallback :: when x { 1 -> 10, else -> 20 }
+            guard :: when { x > 5 -> \"big\", x > 1 -> \"medium\", else -> \"small\" }
+            unmatched :: when x { 1 -> 1 }",
+        );
+        assert_eq!("30", get_var(&evaluator, "subject"));
+        assert_eq!("20", get_var(&evaluator, "fallback"));
+        assert_eq!("medium", get_var(&evaluator, "guard"));
+        assert_eq!("()", get_var(&evaluator, "unmatched"));
+    }
+
+    #[test]
+    fn test_patterns() {
+        let evaluator = eval(
+            "Color :: enum { Red, Green }
+            describe :: func(val) {
+                when val {
+                    -1 -> \"minus one\"
+                    0..10 -> \"digit\"
+                    none -> \"nothing\"
+                    [] -> \"empty\"
+                    [first, ...rest, last] -> \"{first} {rest} {last}\"
+                    Color.Red -> \"red\"
+                    _ -> \"other\"
+                }
+            }
+            a :: describe(-1)
+            b :: describe(9.5)
+            c :: describe(10)
+            d :: describe(none)
+            e :: describe([])
+            f :: describe([1, 2, 3, 4])
+            g :: describe(Color.Red)
+            h :: describe(Color.Green)
+            bound :: when [1, 2] { [x] -> x, [x, y] -> x + y }",
+        );
+        assert_eq!("minus one", get_var(&evaluator, "a"));
+        assert_eq!("digit", get_var(&evaluator, "b"));
+        assert_eq!("other", get_var(&evaluator, "c"));
+        assert_eq!("nothing", get_var(&evaluator, "d"));
+        assert_eq!("empty", get_var(&evaluator, "e"));
+        assert_eq!("1 [2, 3] 4", get_var(&evaluator, "f"));
+        assert_eq!("red", get_var(&evaluator, "g"));
+        assert_eq!("other", get_var(&evaluator, "h"));
+        assert_eq!("3", get_var(&evaluator, "bound"));
+        // Bindings of patterns are scoped to their case
+        assert!(evaluator.env.borrow().get(&"x".into()).is_none());
+    }
+
+    #[test]
+    fn test_loops() {
+        let evaluator = eval(
+            "sum :: [0]
+            loop i in 0..5 {
+                sum[0] = sum[0] + i
+            }
+            items :: []
+            loop item in [\"a\", \"b\"] {
+                items :: item
+            }
+            count :: [0]
+            loop count[0] < 3 {
+                count[0] = count[0] + 1
+            }
+            branch :: [\"none\"]
+            loop i in 0..0 {
+                branch[0] = \"for\"
+            } else loop count[0] > 5 {
+                branch[0] = \"while\"
+            } else {
+                branch[0] = \"else\"
+            }
+            skipped :: [\"else\"]
+            loop i in [1] {
+                skipped[0] = \"for\"
+            } else {
+                skipped[0] = \"else\"
+            }
+            contains :: [2 in 0..3, 3 in 0..3, \"b\" in [\"a\", \"b\"], \"ell\" in \"hello\"]",
+        );
+        assert_eq!("[10]", get_var(&evaluator, "sum"));
+        // Bindings in the block of a loop are scoped to the iteration
+        assert_eq!("[]", get_var(&evaluator, "items"));
+        assert_eq!("[3]", get_var(&evaluator, "count"));
+        assert_eq!("[else]", get_var(&evaluator, "branch"));
+        assert_eq!("[for]", get_var(&evaluator, "skipped"));
+        assert_eq!("[true, false, true, true]", get_var(&evaluator, "contains"));
+    }
+
+    #[test]
+    fn test_control_flow() {
+        let evaluator = eval(
+            "found :: loop i in 0..10 {
+                if i * i > 20 {
+                    break i
+                }
+            }
+            odd_sum :: [0]
+            loop i in 0..6 {
+                when i % 2 {
+                    0 -> {
+                        continue
+                    }
+                }
+                odd_sum[0] = odd_sum[0] + i
+            }
+            pair :: outer: loop x in 1..5 {
+                loop y in 1..5 {
+                    if x * y == 6 {
+                        break outer [x, y]
+                    }
+                    if y > x {
+                        continue outer
+                    }
+                }
+            }
+            skipped :: loop i in [] { break 1 } else { 2 }
+            first_neg :: func(xs) {
+                loop x in xs {
+                    if x < 0 {
+                        return x
+                    }
+                }
+                return none
+            }
+            neg :: first_neg([3, -2, -5])
+            no_neg :: first_neg([1])",
+        );
+        assert_eq!("5", get_var(&evaluator, "found"));
+        assert_eq!("[9]", get_var(&evaluator, "odd_sum"));
+        assert_eq!("[2, 3]", get_var(&evaluator, "pair"));
+        assert_eq!("2", get_var(&evaluator, "skipped"));
+        assert_eq!("-2", get_var(&evaluator, "neg"));
+        assert_eq!("none", get_var(&evaluator, "no_neg"));
+    }
+
+    #[test]
+    fn test_signals_in_exprs() {
+        let evaluator = eval(
+            "total := 0
+            loop i in 0..4 {
+                total += when i {
+                    0 -> {
+                        continue
+                    }
+                    else -> i
+                }
+            }
+            f :: func() {
+                print(when 1 { 1 -> { return 9 } })
+                3
+            }
+            returned :: f()
+            nested :: loop {
+                [1, 2 + if true { break \"inner\" }]
+            }
+            guarded :: loop i in 0..3 {
+                if i > 0 and when i { 2 -> { break i }, else -> false } {
+                    break 0
+                }
+            }",
+        );
+        assert_eq!("6", get_var(&evaluator, "total"));
+        assert_eq!("9", get_var(&evaluator, "returned"));
+        assert_eq!("inner", get_var(&evaluator, "nested"));
+        assert_eq!("2", get_var(&evaluator, "guarded"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot break outside of a loop")]
+    fn test_break_outside_loop() {
+        eval("break");
+    }
+
+    #[test]
+    fn test_logical_ops() {
+        let evaluator = eval(
+            "x :: 4
+            both :: x > 1 and x < 5
+            either :: x == 1 or !(x == 2)
+            mixed :: false or true and x == 4
+            skipped_and :: false and missing
+            skipped_or :: true or 1 / 0 == 0
+            guard :: when { x > 1 and x % 2 == 1 -> \"odd\", x > 1 or x < 0 -> \"even\" }",
+        );
+        assert_eq!("true", get_var(&evaluator, "both"));
+        assert_eq!("true", get_var(&evaluator, "either"));
+        assert_eq!("true", get_var(&evaluator, "mixed"));
+        assert_eq!("false", get_var(&evaluator, "skipped_and"));
+        assert_eq!("true", get_var(&evaluator, "skipped_or"));
+        assert_eq!("even", get_var(&evaluator, "guard"));
+    }
+
+    #[test]
+    fn test_conversions() {
+        let evaluator = eval(
+            "x :: 7.9
+            truncated :: x as Int
+            parsed :: \" 12 \" as Int + 1
+            float :: 3 as Num / 2
+            parsed_float :: \"2.5\" as Num
+            text :: (1 + 2) as Str == \"3\"
+            joined :: \"{x as Int as Str}!\"",
+        );
+        assert_eq!("7", get_var(&evaluator, "truncated"));
+        assert_eq!("13", get_var(&evaluator, "parsed"));
+        assert_eq!("1.5", get_var(&evaluator, "float"));
+        assert_eq!("2.5", get_var(&evaluator, "parsed_float"));
+        assert_eq!("true", get_var(&evaluator, "text"));
+        assert_eq!("7!", get_var(&evaluator, "joined"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot convert abc to Int")]
+    fn test_invalid_conversion() {
+        eval("\"abc\" as Int");
+    }
+
+    #[test]
+    #[should_panic(expected = "The operands of `and` need to be booleans, got: 1")]
+    fn test_logical_non_bool() {
+        eval("true and 1");
+    }
+
+    #[test]
+    fn test_assignments() {
+        let evaluator = eval(
+            "x := 1
+            y := x = 5
+            sum := 0
+            loop i in 1..5 {
+                sum += i
+            }
+            xs :: [1, 2]
+            xs[1] *= 10
+            ratio := 7
+            ratio /= 2.0
+            rest := 10
+            rest %= 4
+            rest -= 1",
+        );
+        assert_eq!("5", get_var(&evaluator, "x"));
+        assert_eq!("5", get_var(&evaluator, "y"));
+        assert_eq!("10", get_var(&evaluator, "sum"));
+        assert_eq!("[1, 20]", get_var(&evaluator, "xs"));
+        assert_eq!("3.5", get_var(&evaluator, "ratio"));
+        assert_eq!("1", get_var(&evaluator, "rest"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot assign to the constant x")]
+    fn test_const_assignment() {
+        eval("x :: 1\nloop i in 0..1 {\n    x += i\n}");
+    }
+
+    #[test]
+    #[should_panic(expected = "Could not find identifier: y")]
+    fn test_undefined_assignment() {
+        eval("y = 1");
+    }
+
+    #[test]
+    fn test_use() {
+        let evaluator = eval_with_modules(
+            &[
+                ("math.consts", "pi :: 3.14\ne :: 2.72"),
+                (
+                    "util",
+                    "double :: func(x) { return x * 2 }\ncolors :: [1, 2]",
+                ),
+                (
+                    "scaling",
+                    "local base :: 2\nscale :: func(x) { x * base }\nquad :: func(x) { scale(scale(x)) }",
+                ),
+            ],
+            "use math.consts
+            use math.consts as c
+            use util.{double, colors}
+            use scaling
+            use scaling.{quad}
+            base :: 100
+            pi :: consts.pi
+            e :: c.e
+            doubled :: double(21)
+            scaled :: scaling.scale(3)
+            quadrupled :: quad(3)",
+        );
+        assert_eq!("3.14", get_var(&evaluator, "pi"));
+        assert_eq!("2.72", get_var(&evaluator, "e"));
+        assert_eq!("42", get_var(&evaluator, "doubled"));
+        // Functions use the bindings of their own module
+        assert_eq!("6", get_var(&evaluator, "scaled"));
+        assert_eq!("12", get_var(&evaluator, "quadrupled"));
+        assert_eq!("[1, 2]", get_var(&evaluator, "colors"));
+        assert_eq!("module math.consts", get_var(&evaluator, "c"));
+    }
+
+    #[test]
+    fn test_local() {
+        let modules = [
+            ("util", "double :: 2"),
+            (
+                "config",
+                "local secret :: 40\nlocal use util.{double}\nport :: secret + double",
+            ),
+        ];
+        let evaluator = eval_with_modules(
+            &modules,
+            "use config
+            use config.{port}
+            local own :: 1
+            total :: config.port + own",
+        );
+        assert_eq!("42", get_var(&evaluator, "port"));
+        assert_eq!("43", get_var(&evaluator, "total"));
+    }
+
+    #[test]
+    #[should_panic(expected = "secret is local to the module config")]
+    fn test_import_local() {
+        eval_with_modules(&[("config", "local secret :: 40")], "use config.{secret}");
+    }
+
+    #[test]
+    #[should_panic(expected = "secret is local to the module config")]
+    fn test_redeclare_local() {
+        eval_with_modules(
+            &[("config", "local secret :: 40\nsecret :: 41")],
+            "use config.{secret}",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "double is local to the module config")]
+    fn test_field_of_local() {
+        eval_with_modules(
+            &[
+                ("util", "double :: 2"),
+                ("config", "local use util.{double}"),
+            ],
+            "use config\nx :: config.double",
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Could not find module: math")]
+    fn test_use_unknown_module() {
+        eval("use math");
+    }
+
+    #[test]
+    #[should_panic(expected = "Module util has no binding triple")]
+    fn test_use_unknown_binding() {
+        eval_with_modules(&[("util", "double :: 2")], "use util.{triple}");
+    }
+
+    #[test]
+    fn test_module_files() {
+        let evaluator = eval_with_roots(
+            &[
+                "tests/evaluator/modules/override",
+                "tests/evaluator/modules",
+            ],
+            "use shapes.circle
+            use counter as a
+            use counter as b
+            a.items[0] = 5
+            pi :: circle.pi
+            shared :: b.items[0]
+            use plugins.naming
+            use plugins.naming.{describe}
+            direct :: naming.label(\"a\")
+            imported :: describe(\"b\")",
+        );
+        // Earlier roots are searched first
+        assert_eq!("3", get_var(&evaluator, "pi"));
+        // Both imports are the same module from the later root
+        assert_eq!("5", get_var(&evaluator, "shared"));
+        // Functions can use the other bindings of their file
+        assert_eq!("plugin: a", get_var(&evaluator, "direct"));
+        assert_eq!("plugin: b", get_var(&evaluator, "imported"));
+    }
+
+    #[test]
+    fn test_memory_modules() {
+        let mut provider = MemoryProvider::new();
+        provider.insert("plugins/greet/words.nx", "hello :: \"hi\"");
+        provider.insert(
+            "plugins/greet.nx",
+            "use greet.words\nmessage :: words.hello",
+        );
+        let mut evaluator = Evaluator::new();
+        evaluator.loader = ModuleLoader::with_provider(provider);
+        evaluator.loader.add_root("plugins");
+        evaluator.eval_program(parse("use greet.{message}"));
+        assert_eq!("hi", get_var(&evaluator, "message"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Circular import of module cycle_a: cycle_a -> cycle_b -> cycle_a")]
+    fn test_circular_import() {
+        eval_with_roots(&["tests/evaluator/modules"], "use cycle_a");
+    }
+
+    #[test]
+    fn test_containers() {
+        let evaluator = eval(
+            "Color :: enum { Red, Green }
+            Point :: container { x: Num, y: Int, color: Color }
+            Empty :: container
+            y := 2
+            p :: Point { x: 1.5, y, color: Color.Red }
+            q :: p
+            q.x *= 2
+            p.y = 5
+            sum :: p.x + p.y
+            quadrant :: when p {
+                Point { x: 0, y: 0 } -> \"origin\"
+                Point { x, color: Color.Red } -> \"red {x}\"
+                else -> \"other\"
+            }
+            listed :: if p in [Point { x: 3.0, y: 5, color: Color.Red }] { true } else { false }
+            empty :: Empty {}",
+        );
+        assert_eq!("Point { x: 3, y: 5, color: Red }", get_var(&evaluator, "p"));
+        assert_eq!("8", get_var(&evaluator, "sum"));
+        assert_eq!("red 3", get_var(&evaluator, "quadrant"));
+        assert_eq!("true", get_var(&evaluator, "listed"));
+        assert_eq!(
+            "container { x: Num, y: Int, color: Color }",
+            get_var(&evaluator, "Point")
+        );
+        assert_eq!("Empty {}", get_var(&evaluator, "empty"));
+        assert_eq!("container", get_var(&evaluator, "Empty"));
+    }
+
+    #[test]
+    #[should_panic(expected = "The field y of Point needs to be Int, got: 1.5")]
+    fn test_field_type() {
+        eval("Point :: container { x, y: Int }\np :: Point { x: \"any\", y: 1.5 }");
+    }
+
+    #[test]
+    #[should_panic(expected = "The field y of Point needs to be Int, got: text")]
+    fn test_field_assign_type() {
+        eval("Point :: container { y: Int }\np :: Point { y: 1 }\np.y = \"text\"");
+    }
+
+    #[test]
+    #[should_panic(expected = "Missing field y of Point")]
+    fn test_missing_field() {
+        eval("Point :: container { x, y }\np :: Point { x: 1 }");
+    }
+
+    #[test]
+    #[should_panic(expected = "Point has no field called z")]
+    fn test_unknown_field() {
+        eval("Point :: container { x }\np :: Point { x: 1 }\nz :: p.z");
+    }
+
+    fn eval(source: &str) -> Evaluator {
+        eval_with_modules(&[], source)
+    }
+
+    /// Evaluates the source after adding each of
+    /// the modules as a path and its source
+    fn eval_with_modules(modules: &[(&str, &str)], source: &str) -> Evaluator {
+        let mut evaluator = Evaluator::new();
+        for (path, module) in modules {
+            let module = parse(module);
+            evaluator.add_module(path, module);
+        }
+        evaluator.eval_program(parse(source));
+        evaluator
+    }
+
+    fn eval_with_roots(roots: &[&str], source: &str) -> Evaluator {
+        let mut evaluator = Evaluator::new();
+        for root in roots {
+            evaluator.loader.add_root(root);
+        }
+        evaluator.eval_program(parse(source));
+        evaluator
+    }
+
+    fn parse(source: &str) -> Program {
+        let mut lexer = Lexer::from_string(source, Some("test.nx"));
+        let mut parser = Parser::new(&mut lexer);
+        parser.parse_program().expect("Failed to parse")
+    }
+
+    fn get_var(evaluator: &Evaluator, name: &str) -> String {
+        match evaluator.env.borrow().get(&name.into()) {
+            Some(obj) => obj.obj.to_string(),
+            None => panic!("Could not find variable: {}", name),
+        }
+    }
+}
@@ -1,148 +1,519 @@
+pub mod source;
 mod tests;
 pub mod tokens;
 
-use clutils::{errors::FileHandlerError, files::FileHandler};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::Display,
+    io::{self, Read},
+    mem::take,
+    path::Path,
+    rc::Rc,
+};
+
+use source::{FsProvider, SourceProvider};
 use tokens::*;
 
+/// Name used for sources that were not given a file name
+pub const ANONYMOUS_SOURCE: &str = "<anonymous>";
 
 pub struct Lexer {
-    filehandler: FileHandler,
+    input: String,
+    file_name: Rc<str>,
     cur_char: Option<char>,
+    /// Byte offset of the current char
     cur_pos: usize,
+    /// Byte offset of the char after the current one
     next_pos: usize,
+    /// Line of the current char, starting at 1
+    line: usize,
+    /// Column of the current char, starting at 1
+    col: usize,
+    /// Whether whitespace and comments are attached to tokens
+    keep_trivia: bool,
+    /// Trivia that was found since the last token
+    trivia: Vec<Trivia>,
+    /// Whether the iterator already returned `Eof`
+    finished: bool,
+    /// Whether a token was found on the current line
+    line_has_tok: bool,
 }
 
 impl Lexer {
-    pub fn new(path: &String) -> Result<Self, FileHandlerError> {
-        let filehandler = FileHandler::new(path)?;
+    /// Create a lexer for the file at `path`
+    pub fn new(path: &String) -> io::Result<Self> {
+        Self::from_provider(&FsProvider, path)
+    }
+
+    /// Create a lexer for the source the provider has at `path`
+    pub fn from_provider(
+        provider: &dyn SourceProvider,
+        path: impl AsRef<Path>,
+    ) -> io::Result<Self> {
+        let path = path.as_ref();
+        let input = provider.read(path)?;
+        Ok(Self::from_string(input, Some(&path.to_string_lossy())))
+    }
+
+    /// Create a lexer for in-memory source code.
+    /// `file_name` is a virtual name used to identify the source
+    pub fn from_string(input: impl Into<String>, file_name: Option<&str>) -> Self {
         let mut lexer = Self {
-            filehandler,
+            input: input.into(),
+            file_name: file_name.unwrap_or(ANONYMOUS_SOURCE).into(),
             cur_char: None,
             cur_pos: 0,
             next_pos: 0,
+            line: 1,
+            col: 0,
+            keep_trivia: false,
+            trivia: Vec::new(),
+            finished: false,
+            line_has_tok: false,
         };
         lexer.next_char();
-        Ok(lexer)
+        lexer
     }
 
-    pub fn tokenize(&mut self) -> Option<Token> {
-        self.skip_whitespace();
-        Some(match self.cur_char {
+    /// Create a lexer by reading all of `reader` into memory
+    pub fn from_reader<R: Read>(mut reader: R, file_name: Option<&str>) -> io::Result<Self> {
+        let mut input = String::new();
+        reader.read_to_string(&mut input)?;
+        Ok(Self::from_string(input, file_name))
+    }
+
+    pub fn file_name(&self) -> &str {
+        &self.file_name
+    }
+
+    pub fn source(&self) -> &str {
+        &self.input
+    }
+
+    /// Attach whitespace, comments and newlines that are skipped
+    /// to the token that follows them, so the source can be rebuilt
+    /// from the tokens. Trivia is dropped by default
+    pub fn set_keep_trivia(&mut self, keep_trivia: bool) {
+        self.keep_trivia = keep_trivia;
+    }
+
+    /// Tokenize the next token. Invalid input is skipped
+    /// after returning an error for it, so the lexer can
+    /// be called again to continue after the error.
+    /// Returns `Eof` for every call after the end of the source
+    pub fn tokenize(&mut self) -> Result<SpannedToken, LexError> {
+        self.skip_trivia();
+        let start = self.location();
+        let tok = self.tokenize_tok();
+        let span = self.span_from(start);
+        let trivia = take(&mut self.trivia);
+        self.line_has_tok = !matches!(tok, Ok(Token::Eol));
+        match tok {
+            Ok(tok) => Ok(SpannedToken { tok, span, trivia }),
+            Err(kind) => Err(LexError { kind, span, trivia }),
+        }
+    }
+
+    /// Byte offset, line and column of the current char
+    fn location(&self) -> (usize, usize, usize) {
+        (self.cur_pos, self.line, self.col)
+    }
+
+    /// Span from the `start` position up to the current char
+    fn span_from(&self, (start, line, col): (usize, usize, usize)) -> Span {
+        Span {
+            file: Rc::clone(&self.file_name),
+            start,
+            end: self.cur_pos,
+            line,
+            col,
+        }
+    }
+
+    /// Record the source from the `start` position up to
+    /// the current char as trivia if trivia is kept
+    fn push_trivia(&mut self, kind: TriviaKind, start: (usize, usize, usize)) {
+        if self.keep_trivia && start.0 != self.cur_pos {
+            let span = self.span_from(start);
+            self.trivia.push(Trivia {
+                kind,
+                text: self.input[span.range()].into(),
+                span,
+            });
+        }
+    }
+
+    fn tokenize_tok(&mut self) -> Result<Token, LexErrorKind> {
+        Ok(match self.cur_char {
             Some(ch) => match ch {
                 '\n' => {
                     self.next_char();
                     Token::Eol
                 }
-                c if c.is_numeric() => self.tokenize_num(),
+                'r' if self.peek_char() == Some('"') => {
+                    self.next_char();
+                    self.tokenize_string(true)?
+                }
+                c if c.is_numeric() => self.tokenize_num()?,
                 c if c.is_alphabetic() || c == '_' => self.tokenize_ident(),
-                _ => return self.tokenize_symbol(),
+                _ => return self.tokenize_symbol(ch),
             },
             None => Token::Eof,
         })
     }
 
-    fn tokenize_num(&mut self) -> Token {
-        // TODO: floating points
+    fn tokenize_num(&mut self) -> Result<Token, LexErrorKind> {
         let first_pos = self.cur_pos;
-        let mut found_fp = false;
+        let radix = match (self.cur_char, self.peek_char()) {
+            (Some('0'), Some('x' | 'X')) => 16,
+            (Some('0'), Some('o' | 'O')) => 8,
+            (Some('0'), Some('b' | 'B')) => 2,
+            _ => 10,
+        };
+
+        if radix != 10 {
+            self.next_char();
+            self.next_char();
+            let digits_pos = self.cur_pos;
+            // Take all alphanumeric chars so invalid digits are part of the error
+            self.skip_digits(char::is_alphanumeric);
+            let digits = Self::remove_separators(&self.input[digits_pos..self.cur_pos]);
+            // Prefixed literals are bit patterns, so all 64 bits can be used
+            return match u64::from_str_radix(&digits, radix) {
+                Ok(num) => Ok(Token::Literal(Literal::Int(num as i64))),
+                Err(_) => Err(LexErrorKind::InvalidNumber(
+                    self.input[first_pos..self.cur_pos].into(),
+                )),
+            };
+        }
+
+        let mut is_float = false;
+        self.skip_digits(char::is_numeric);
+        // Only treat the dot as a decimal point if a digit follows,
+        // to keep ranges like `0..10` intact
+        if self.cur_char == Some('.') && self.peek_char().is_some_and(|ch| ch.is_ascii_digit()) {
+            is_float = true;
+            self.next_char();
+            self.skip_digits(char::is_numeric);
+        }
+        if matches!(self.cur_char, Some('e' | 'E')) {
+            let digit_offset = match self.peek_char() {
+                Some('+' | '-') => 1,
+                _ => 0,
+            };
+            if self
+                .peek_nth_char(digit_offset)
+                .is_some_and(|ch| ch.is_ascii_digit())
+            {
+                is_float = true;
+                (0..=digit_offset).for_each(|_| self.next_char());
+                self.skip_digits(char::is_numeric);
+            }
+        }
+
+        let string = Self::remove_separators(&self.input[first_pos..self.cur_pos]);
+        let lit = match is_float {
+            true => string.parse().map(Literal::Num).ok(),
+            false => string.parse().map(Literal::Int).ok(),
+        };
+        match lit {
+            Some(lit) => Ok(Token::Literal(lit)),
+            None => Err(LexErrorKind::InvalidNumber(string)),
+        }
+    }
+
+    /// Skip all chars that are digits according to `is_digit` or underscores
+    fn skip_digits(&mut self, is_digit: impl Fn(char) -> bool) {
         while let Some(ch) = self.cur_char {
-            if ch.is_numeric() || ch == '_' {
+            if !is_digit(ch) && ch != '_' {
+                break;
+            }
+            self.next_char();
+        }
+    }
+
+    /// Remove all underscores to ensure that parsing works
+    fn remove_separators(num: &str) -> String {
+        num.chars().filter(|&c| c != '_').collect()
+    }
+
+    fn tokenize_symbol(&mut self, ch: char) -> Result<Token, LexErrorKind> {
+        let ret = match ch {
+            '=' => match self.peek_char() {
+                Some('=') => {
+                    self.next_char();
+                    Token::Operator(Operator::Equals)
+                }
+                Some('>') => {
+                    self.next_char();
+                    Token::Arrow
+                }
+                _ => Token::Assign,
+            },
+            '+' => self.tokenize_operator(Operator::Plus),
+            '-' => match self.peek_char() {
+                Some('>') => {
+                    self.next_char();
+                    Token::ThinArrow
+                }
+                _ => self.tokenize_operator(Operator::Minus),
+            },
+            '*' => self.tokenize_operator(Operator::Asterisk),
+            '/' => self.tokenize_operator(Operator::Slash),
+            '%' => self.tokenize_operator(Operator::Percent),
+            '!' => match self.peek_char() {
+                Some('=') => {
+                    self.next_char();
+                    Token::Operator(Operator::NotEquals)
+                }
+                _ => Token::ExclamMark,
+            },
+            '?' => match self.peek_char() {
+                Some('.') => {
+                    self.next_char();
+                    Token::SafeDot
+                }
+                _ => Token::QuestionMark,
+            },
+            '>' => match self.peek_char() {
+                Some('=') => {
+                    self.next_char();
+                    Token::Operator(Operator::GreaterEquals)
+                }
+                _ => Token::Operator(Operator::Greater),
+            },
+            '<' => match self.peek_char() {
+                Some('=') => {
+                    self.next_char();
+                    Token::Operator(Operator::LesserEquals)
+                }
+                _ => Token::Operator(Operator::Lesser),
+            },
+            ';' => Token::Eol,
+            '(' => Token::LParent,
+            ')' => Token::RParent,
+            '{' => Token::LCurly,
+            '}' => Token::RCurly,
+            '[' => Token::LSquare,
+            ']' => Token::RSquare,
+            '@' => Token::At,
+            '"' => return self.tokenize_string(false),
+            ':' => match self.peek_char() {
+                Some(':') => {
+                    self.next_char();
+                    Token::ConstAssign
+                }
+                Some('=') => {
+                    self.next_char();
+                    Token::VarAssign
+                }
+                _ => Token::Colon,
+            },
+            ',' => Token::Comma,
+            '.' => match (self.peek_char(), self.peek_nth_char(1)) {
+                (Some('.'), Some('.')) => {
+                    self.next_char();
+                    self.next_char();
+                    Token::Ellipsis
+                }
+                (Some('.'), _) => {
+                    self.next_char();
+                    Token::Range
+                }
+                _ => Token::Dot,
+            },
+            _ => {
                 self.next_char();
-            } else if !found_fp && ch == '.' {
-                found_fp = true;
+                return Err(LexErrorKind::InvalidSymbol(ch));
+            }
+        };
+        self.next_char();
+        Ok(ret)
+    }
+
+    /// Current char needs to be the char of the operator.
+    /// Tokenizes compound assignments like `+=` as well
+    fn tokenize_operator(&mut self, op: Operator) -> Token {
+        match self.peek_char() {
+            Some('=') => {
                 self.next_char();
-            } else {
-                break;
+                Token::OpAssign(op)
             }
+            _ => Token::Operator(op),
         }
-        let string: String = self.filehandler.content[first_pos..self.cur_pos].into();
-        // Remove all underscores to ensure that parsing works
-        let string: String = string.chars().filter(|&c| c != '_').collect();
-        Token::Literal(Literal::Num(
-            string
-                .parse()
-                .expect(&format!("Failed to parse string: {} to an integer", string)),
-        ))
     }
 
-    fn tokenize_symbol(&mut self) -> Option<Token> {
-        let ret = match self.cur_char {
-            Some(ch) => match ch {
-                '=' => match self.filehandler.content.chars().nth(self.next_pos) {
-                    Some('=') => {
-                        self.next_char();
-                        Token::Operator(Operator::Equals)
-                    }
-                    Some('>') => {
-                        self.next_char();
-                        Token::Arrow
-                    }
-                    _ => Token::Assign,
-                },
-                '+' => Token::Operator(Operator::Plus),
-                '-' => Token::Operator(Operator::Minus),
-                '!' => Token::ExclamMark,
-                '*' => Token::Operator(Operator::Asterisk),
-                '/' => Token::Operator(Operator::Slash),
-                '>' => match self.filehandler.content.chars().nth(self.next_pos) {
-                    Some('=') => {
-                        self.next_char();
-                        Token::Operator(Operator::GreaterEquals)
-                    }
-                    _ => Token::Operator(Operator::Greater),
-                },
-                '<' => match self.filehandler.content.chars().nth(self.next_pos) {
-                    Some('=') => {
-                        self.next_char();
-                        Token::Operator(Operator::LesserEquals)
+    /// Current char needs to be the opening quote. Strings
+    /// opened with three quotes can span multiple lines and
+    /// raw strings neither support escapes nor interpolation.
+    /// Sets the current char to the char after the closing quotes
+    fn tokenize_string(&mut self, raw: bool) -> Result<Token, LexErrorKind> {
+        let multi_line = self.peek_char() == Some('"') && self.peek_nth_char(1) == Some('"');
+        let quotes = if multi_line { 3 } else { 1 };
+        (0..quotes).for_each(|_| self.next_char());
+
+        let mut chars = Vec::new();
+        let mut embedded = HashMap::new();
+        // Errors inside the string are returned after the
+        // closing quotes, so lexing continues after the string
+        let mut error = None;
+        loop {
+            match self.cur_char {
+                None => return Err(LexErrorKind::UnterminatedString),
+                Some('\n') if !multi_line => return Err(LexErrorKind::UnterminatedString),
+                Some('"') if !multi_line || self.peek_is_triple_quote() => break,
+                Some('\\') if !raw => match self.tokenize_escape() {
+                    Ok(ch) => chars.push(ch),
+                    Err(err) => {
+                        error.get_or_insert(err);
                     }
-                    _ => Token::Operator(Operator::Lesser),
                 },
-                ';' => Token::Eol,
-                '(' => Token::LParent,
-                ')' => Token::RParent,
-                '{' => Token::LCurly,
-                '}' => Token::RCurly,
-                '"' => self.tokenize_string(),
-                ':' => match self.filehandler.content.chars().nth(self.next_pos) {
-                    Some(':') => {
-                        self.next_char();
-                        Token::ConstAssign
-                    }
-                    Some('=') => {
-                        self.next_char();
-                        Token::VarAssign
+                Some('{') if !raw => {
+                    let begin = chars.len();
+                    match self.tokenize_embedded(&mut chars, multi_line) {
+                        Ok(toks) => {
+                            embedded.insert(begin..chars.len(), toks);
+                        }
+                        Err(err) => {
+                            error.get_or_insert(err);
+                        }
                     }
-                    _ => Token::Colon,
-                },
-                ',' => Token::Comma,
-                '.' => Token::Dot,
-                '#' => return self.tokenize_comment(),
-                _ => panic!("Invalid symbol: {:?}", &self.cur_char),
-            },
-            None => todo!(),
+                }
+                Some(ch) => {
+                    chars.push(ch);
+                    self.next_char();
+                }
+            }
+        }
+        (0..quotes).for_each(|_| self.next_char());
+
+        if let Some(err) = error {
+            return Err(err);
+        }
+        Ok(Token::Literal(match embedded.is_empty() {
+            true => Literal::Str(chars.into_iter().collect()),
+            false => Literal::Interpolated((chars, Some(embedded))),
+        }))
+    }
+
+    /// Current char needs to be the backslash that starts the escape sequence.
+    /// Sets the current char to the char after the escape sequence
+    fn tokenize_escape(&mut self) -> Result<char, LexErrorKind> {
+        self.next_char();
+        let escaped = match self.cur_char {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some('0') => '\0',
+            Some(ch @ ('\\' | '"' | '\'' | '{' | '}')) => ch,
+            Some('u') => return self.tokenize_unicode_escape(),
+            Some(ch) => {
+                self.next_char();
+                return Err(LexErrorKind::InvalidEscape(ch));
+            }
+            None => return Err(LexErrorKind::UnterminatedString),
         };
         self.next_char();
-        Some(ret)
+        Ok(escaped)
     }
 
-    fn tokenize_string(&mut self) -> Token {
+    /// Current char needs to be the `u` of a unicode
+    /// escape sequence like `\u{1F980}`
+    fn tokenize_unicode_escape(&mut self) -> Result<char, LexErrorKind> {
         self.next_char();
-        let begin_pos = self.cur_pos;
-        while self.filehandler.content.chars().nth(self.cur_pos) != Some('"') {
-            if self.filehandler.content.chars().nth(self.cur_pos) == Some('{') {
-                self.next_char();
-                while self.filehandler.content.chars().nth(self.cur_pos) != Some('}') {
+        if self.cur_char != Some('{') {
+            return Err(LexErrorKind::InvalidUnicodeEscape(String::new()));
+        }
+        self.next_char();
+        let mut digits = String::new();
+        while let Some(ch) = self.cur_char {
+            if !ch.is_ascii_hexdigit() {
+                break;
+            }
+            digits.push(ch);
+            self.next_char();
+        }
+        if self.cur_char != Some('}') {
+            return Err(LexErrorKind::InvalidUnicodeEscape(digits));
+        }
+        self.next_char();
+        u32::from_str_radix(&digits, 16)
+            .ok()
+            .filter(|_| digits.len() <= 6)
+            .and_then(char::from_u32)
+            .ok_or(LexErrorKind::InvalidUnicodeEscape(digits))
+    }
+
+    /// Current char needs to be the `{` that opens an embedded expression.
+    /// Pushes the expression including its braces to `chars` and
+    /// sets the current char to the char after the closing `}`.
+    /// The tokens end with an `Eof` at the closing `}`
+    fn tokenize_embedded(
+        &mut self,
+        chars: &mut Vec<char>,
+        multi_line: bool,
+    ) -> Result<Vec<SpannedToken>, LexErrorKind> {
+        let begin_pos = self.next_pos;
+        let begin = (begin_pos, self.line, self.col + 1);
+        let mut depth = 0;
+        let mut in_str = false;
+        loop {
+            let ch = match self.cur_char {
+                Some('\n') if !multi_line => None,
+                ch => ch,
+            };
+            let Some(ch) = ch else {
+                return Err(LexErrorKind::UnterminatedInterpolation);
+            };
+            chars.push(ch);
+            match ch {
+                '"' => in_str = !in_str,
+                // Skip escaped chars of nested strings
+                '\\' if in_str => {
                     self.next_char();
+                    chars.extend(self.cur_char);
+                }
+                '{' if !in_str => depth += 1,
+                '}' if !in_str => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
                 }
+                _ => (),
             }
             self.next_char();
         }
-        let string = &self.filehandler.content[begin_pos..self.cur_pos];
-        Token::Literal(Literal::Str(string.into()))
+        let lexer = Lexer::from_string(&self.input[begin_pos..self.cur_pos], Some(&self.file_name));
+        self.next_char();
+
+        lexer
+            .map(|tok| {
+                tok.map(|mut tok| {
+                    Self::shift_tok(&mut tok, begin);
+                    tok
+                })
+                .map_err(|err| err.kind)
+            })
+            .collect()
+    }
+
+    /// Spans of embedded tokens start at the beginning of the
+    /// embedded expression and need to be moved into this source
+    fn shift_tok(tok: &mut SpannedToken, begin: (usize, usize, usize)) {
+        tok.span.shift(begin);
+        if let Token::Literal(Literal::Interpolated((_, Some(embedded)))) = &mut tok.tok {
+            embedded
+                .values_mut()
+                .flatten()
+                .for_each(|tok| Self::shift_tok(tok, begin));
+        }
     }
 
-    fn tokenize_comment(&mut self) -> Option<Token> {
+    /// Current char needs to be the `#` that starts the comment
+    fn skip_comment(&mut self) {
+        let start = self.location();
         self.next_char();
         while let Some(cur_ch) = self.cur_char {
             if cur_ch != '\n' && cur_ch != '#' {
@@ -151,8 +522,20 @@ impl Lexer {
                 break;
             }
         }
-        self.next_char();
-        None
+        // Encased comments end with the closing `#` and all-line
+        // comments before the newline. The newline still ends the
+        // line if a token is in front of the comment
+        if self.cur_char == Some('#') {
+            self.next_char();
+            self.push_trivia(TriviaKind::Comment, start);
+        } else if self.line_has_tok {
+            self.push_trivia(TriviaKind::Comment, start);
+        } else {
+            self.push_trivia(TriviaKind::Comment, start);
+            let newline = self.location();
+            self.next_char();
+            self.push_trivia(TriviaKind::Newline, newline);
+        }
     }
 
     fn tokenize_ident(&mut self) -> Token {
@@ -164,12 +547,12 @@ impl Lexer {
                 break;
             }
         }
-        let ident: String = self.filehandler.content[first_pos..self.cur_pos].into();
-        return match ident.as_str() {
+        let ident: String = self.input[first_pos..self.cur_pos].into();
+        match ident.as_str() {
             "var" => Token::Var,
             "const" => Token::Const,
             "func" => Token::Func,
-            "struct" => Token::Struct,
+            "container" => Token::Struct,
             "enum" => Token::Enum,
             "use" => Token::Use,
 
@@ -180,36 +563,157 @@ impl Lexer {
 
             "and" => Token::And,
             "or" => Token::Or,
+            "as" => Token::As,
+            "in" => Token::In,
 
             "return" => Token::Return,
             "break" => Token::Break,
+            "continue" => Token::Continue,
             "local" => Token::Local,
 
             "true" => Token::Literal(Literal::Bool(true)),
             "false" => Token::Literal(Literal::Bool(false)),
+            "none" => Token::None,
 
-            _ => Token::Ident(self.filehandler.content[first_pos..self.cur_pos].into()),
-        };
+            _ => Token::Ident(ident),
+        }
     }
 
     fn next_char(&mut self) {
+        match self.cur_char {
+            Some('\n') => {
+                self.line += 1;
+                self.col = 1;
+            }
+            _ => self.col += 1,
+        }
         self.cur_pos = self.next_pos;
-        self.cur_char = self.filehandler.content.chars().nth(self.cur_pos);
-        self.next_pos += 1;
+        self.cur_char = self.input[self.cur_pos..].chars().next();
+        self.next_pos = self.cur_pos + self.cur_char.map_or(0, char::len_utf8);
+    }
+
+    fn peek_char(&self) -> Option<char> {
+        self.peek_nth_char(0)
+    }
+
+    /// Get the nth char after the current char
+    fn peek_nth_char(&self, n: usize) -> Option<char> {
+        self.input[self.next_pos..].chars().nth(n)
+    }
+
+    fn peek_is_triple_quote(&self) -> bool {
+        self.cur_char == Some('"')
+            && self.peek_char() == Some('"')
+            && self.peek_nth_char(1) == Some('"')
+    }
+
+    fn skip_trivia(&mut self) {
+        self.skip_whitespace();
+        while self.cur_char == Some('#') {
+            self.skip_comment();
+            self.skip_whitespace();
+        }
     }
 
     fn skip_whitespace(&mut self) {
-        match self.cur_char {
-            Some(mut ch) => {
-                while ch.is_whitespace() && ch != '\n' {
-                    self.next_char();
-                    match self.cur_char {
-                        Some(cch) => ch = cch,
-                        None => (),
-                    }
-                }
+        let start = self.location();
+        while let Some(ch) = self.cur_char {
+            if !ch.is_whitespace() || ch == '\n' {
+                break;
+            }
+            self.next_char();
+        }
+        self.push_trivia(TriviaKind::Whitespace, start);
+    }
+}
+
+/// Iterates over all tokens up to and including `Eof`
+impl Iterator for Lexer {
+    type Item = Result<SpannedToken, LexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        let tok = self.tokenize();
+        if let Ok(SpannedToken {
+            tok: Token::Eof, ..
+        }) = tok
+        {
+            self.finished = true;
+        }
+        Some(tok)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct LexError {
+    pub kind: LexErrorKind,
+    pub span: Span,
+    /// Trivia in front of the invalid input
+    pub trivia: Vec<Trivia>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum LexErrorKind {
+    /// A char that does not start any token
+    InvalidSymbol(char),
+    /// A number literal that could not be parsed. Decimal integers
+    /// have to fit into an `i64` without their sign, so the smallest
+    /// one is `-9223372036854775807`. Prefixed integers can use all
+    /// 64 bits and wrap around, e.g. `0xFFFFFFFFFFFFFFFF` is `-1`
+    InvalidNumber(String),
+    /// A string literal without closing quotes
+    UnterminatedString,
+    /// An embedded expression in a string without a closing `}`
+    UnterminatedInterpolation,
+    /// An unknown escape sequence like `\q`
+    InvalidEscape(char),
+    /// A unicode escape sequence with invalid hex digits
+    InvalidUnicodeEscape(String),
+}
+
+impl LexError {
+    /// Convert the error into an illegal token
+    /// so parsing can continue after it
+    pub fn to_token(&self) -> SpannedToken {
+        let text = match &self.kind {
+            LexErrorKind::InvalidSymbol(ch) => ch.to_string(),
+            LexErrorKind::InvalidNumber(num) => num.clone(),
+            LexErrorKind::UnterminatedString => "\"".into(),
+            LexErrorKind::UnterminatedInterpolation => "{".into(),
+            LexErrorKind::InvalidEscape(ch) => format!("\\{}", ch),
+            LexErrorKind::InvalidUnicodeEscape(digits) => format!("\\u{{{}}}", digits),
+        };
+        SpannedToken {
+            tok: Token::Illegal(text),
+            span: self.span.clone(),
+            trivia: self.trivia.clone(),
+        }
+    }
+}
+
+impl Error for LexError {}
+
+impl Display for LexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at {}", self.kind, self.span)
+    }
+}
+
+impl Display for LexErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LexErrorKind::InvalidSymbol(ch) => write!(f, "Invalid symbol: {:?}", ch),
+            LexErrorKind::InvalidNumber(num) => write!(f, "Invalid number literal: {}", num),
+            LexErrorKind::UnterminatedString => f.write_str("Unterminated string literal"),
+            LexErrorKind::UnterminatedInterpolation => {
+                f.write_str("Unterminated expression in string literal, expected `}`")
+            }
+            LexErrorKind::InvalidEscape(ch) => write!(f, "Invalid escape sequence: \\{}", ch),
+            LexErrorKind::InvalidUnicodeEscape(digits) => {
+                write!(f, "Invalid unicode escape sequence: \\u{{{}}}", digits)
             }
-            None => (),
         }
     }
 }
@@ -0,0 +1,61 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Where source files are read from. The lexer
+/// and the module loader only access files through it
+pub trait SourceProvider {
+    fn read(&self, path: &Path) -> io::Result<String>;
+
+    /// Checks if there is a source file at the path
+    fn exists(&self, path: &Path) -> bool;
+}
+
+/// Reads sources from the filesystem
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FsProvider;
+
+impl SourceProvider for FsProvider {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.is_file()
+    }
+}
+
+/// Sources that are kept in memory, like scripts
+/// that are shipped inside of the host binary
+#[derive(Debug, Default, Clone)]
+pub struct MemoryProvider {
+    sources: HashMap<PathBuf, String>,
+}
+
+impl MemoryProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, path: impl Into<PathBuf>, source: impl Into<String>) {
+        self.sources.insert(path.into(), source.into());
+    }
+}
+
+impl SourceProvider for MemoryProvider {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        match self.sources.get(path) {
+            Some(source) => Ok(source.clone()),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No source at path: {}", path.display()),
+            )),
+        }
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.sources.contains_key(path)
+    }
+}
@@ -1,14 +1,17 @@
 #[cfg(test)]
 mod tests {
-    use crate::{
-        lexer::{Lexer, Literal, Operator, Token},
-        util,
+    use std::collections::HashMap;
+
+    use crate::lexer::{
+        source::{MemoryProvider, SourceProvider},
+        tokens::{Span, SpannedToken, TriviaKind},
+        LexErrorKind, Lexer, Literal, Operator, Token, ANONYMOUS_SOURCE,
     };
 
     #[test]
     fn test_string() {
         let mut lexer = get_lexer("test");
-        lexer.tokenize();
+        lexer.tokenize().expect("Failed to tokenize");
     }
 
     /// Test for checking if literals like numbers, booleans
@@ -23,9 +26,9 @@ mod tests {
                 "Hello, my name is John. I am a comedian entertaining cats",
             ))),
             // Integers
-            Token::Literal(Literal::Num(9875986234.0)),
+            Token::Literal(Literal::Int(9875986234)),
             // Integers with visual seperator
-            Token::Literal(Literal::Num(1254_890.0)),
+            Token::Literal(Literal::Int(1_254_890)),
             // Floats
             Token::Literal(Literal::Num(5643877689.9886)),
             // Booleans
@@ -33,11 +36,9 @@ mod tests {
             Token::Literal(Literal::Bool(false)),
         ];
         for expect in expected {
-            let tok = lexer.tokenize();
-            lexer.tokenize();
-            if let Some(tok) = tok {
-                assert_eq!(expect, tok);
-            }
+            let tok = lexer.tokenize().expect("Failed to tokenize");
+            lexer.tokenize().expect("Failed to tokenize");
+            assert_eq!(expect, tok.tok);
         }
     }
 
@@ -48,7 +49,7 @@ mod tests {
     #[test]
     fn test_keywords() {
         // TODO: UTF-8 support
-        let mut lexer = get_lexer("keywords");
+        let lexer = get_lexer("keywords");
         let expected = [
             Token::Var,
             Token::Enum,
@@ -59,12 +60,18 @@ mod tests {
             Token::Ident(String::from("vari")),
             Token::Ident(String::from("_const")),
             Token::Ident(String::from("iff")),
+            Token::As,
+            Token::In,
+            Token::None,
+            Token::Continue,
+            Token::Struct,
+            Token::Eof,
         ];
-        for expect in expected {
-            let tok = util::get_next_tok(&mut lexer);
-            lexer.tokenize();
-            assert_eq!(expect, tok)
-        }
+        let toks: Vec<_> = lexer
+            .map(|tok| tok.expect("Failed to tokenize").tok)
+            .filter(|tok| *tok != Token::Eol)
+            .collect();
+        assert_eq!(expected.to_vec(), toks);
     }
 
     /// Test for checking if single character
@@ -81,25 +88,400 @@ mod tests {
             Token::Operator(Operator::Equals),
             Token::Operator(Operator::GreaterEquals),
             Token::Arrow,
+            Token::LSquare,
+            Token::RSquare,
+            Token::Operator(Operator::NotEquals),
+            Token::Range,
+            Token::Operator(Operator::Percent),
+            Token::At,
+            Token::QuestionMark,
+            Token::SafeDot,
+            Token::OpAssign(Operator::Plus),
+            Token::OpAssign(Operator::Minus),
+            Token::OpAssign(Operator::Asterisk),
+            Token::OpAssign(Operator::Slash),
+            Token::OpAssign(Operator::Percent),
+            Token::ThinArrow,
+            Token::Ellipsis,
         ];
         for expect in expected {
-            let tok = util::get_next_tok(&mut lexer);
-            lexer.tokenize();
+            let tok = lexer.tokenize().unwrap().tok;
+            lexer.tokenize().expect("Failed to tokenize");
             assert_eq!(expect, tok)
         }
     }
 
+    /// Test for checking if tokens are displayed
+    /// as the source they are tokenized from
+    #[test]
+    fn test_display() {
+        let toks = [
+            Token::LSquare,
+            Token::RSquare,
+            Token::Operator(Operator::NotEquals),
+            Token::Range,
+            Token::Operator(Operator::Percent),
+            Token::At,
+            Token::QuestionMark,
+            Token::SafeDot,
+            Token::As,
+            Token::In,
+            Token::None,
+            Token::OpAssign(Operator::Plus),
+            Token::OpAssign(Operator::Minus),
+            Token::OpAssign(Operator::Asterisk),
+            Token::OpAssign(Operator::Slash),
+            Token::OpAssign(Operator::Percent),
+            Token::ThinArrow,
+            Token::Ellipsis,
+        ];
+        for tok in toks {
+            let mut lexer = Lexer::from_string(tok.to_string(), None);
+            assert_eq!(tok, lexer.tokenize().unwrap().tok);
+        }
+    }
+
     /// Test for checking if both all-line
     /// and encased comments work
     #[test]
     fn test_comments() {
         let mut lexer = get_lexer("comments");
-        let tok = util::get_next_tok(&mut lexer);
+        let tok = lexer.tokenize().unwrap().tok;
         assert_eq!(Token::Var, tok);
-        let next_tok = util::get_next_tok(&mut lexer);
+        // The comment behind `var` does not swallow the end of its line
+        let next_tok = lexer.tokenize().unwrap().tok;
+        assert_eq!(Token::Eol, next_tok);
+        let next_tok = lexer.tokenize().unwrap().tok;
         assert_eq!(Token::Eof, next_tok);
     }
 
+    /// Test for checking if sources that only exist
+    /// in memory are tokenized like files
+    #[test]
+    fn test_in_memory_source() {
+        let mut lexer = Lexer::from_string("var x = 1", Some("snippet.nx"));
+        assert_eq!("snippet.nx", lexer.file_name());
+        assert_eq!(Token::Var, lexer.tokenize().unwrap().tok);
+        assert_eq!(
+            Token::Ident(String::from("x")),
+            lexer.tokenize().unwrap().tok
+        );
+
+        let mut lexer = Lexer::from_reader("true".as_bytes(), None).expect("Failed to read source");
+        assert_eq!(ANONYMOUS_SOURCE, lexer.file_name());
+        assert_eq!(
+            Token::Literal(Literal::Bool(true)),
+            lexer.tokenize().unwrap().tok
+        );
+        assert_eq!(Token::Eof, lexer.tokenize().unwrap().tok);
+    }
+
+    /// Test for checking if tokens carry the
+    /// correct offsets, lines and columns
+    #[test]
+    fn test_spans() {
+        let mut lexer = Lexer::from_string("x := 10\n  print(x)", Some("spans.nx"));
+        let expected = [
+            (Token::Ident(String::from("x")), 0..1, 1, 1),
+            (Token::VarAssign, 2..4, 1, 3),
+            (Token::Literal(Literal::Int(10)), 5..7, 1, 6),
+            (Token::Eol, 7..8, 1, 8),
+            (Token::Ident(String::from("print")), 10..15, 2, 3),
+            (Token::LParent, 15..16, 2, 8),
+        ];
+        for (tok, range, line, col) in expected {
+            let spanned = lexer.tokenize().unwrap();
+            assert_eq!(tok, spanned.tok);
+            assert_eq!(range, spanned.span.range());
+            assert_eq!((line, col), (spanned.span.line, spanned.span.col));
+            assert_eq!("spans.nx", &*spanned.span.file);
+        }
+    }
+
+    /// Test for checking if multi byte chars
+    /// are tokenized and spanned correctly
+    #[test]
+    fn test_utf8() {
+        let mut lexer = Lexer::from_string("\"grüße\" größe", None);
+        let string = lexer.tokenize().unwrap();
+        assert_eq!(
+            Token::Literal(Literal::Str(String::from("grüße"))),
+            string.tok
+        );
+        let ident = lexer.tokenize().unwrap();
+        assert_eq!(Token::Ident(String::from("größe")), ident.tok);
+        assert_eq!((10..17, 9), (ident.span.range(), ident.span.col));
+    }
+
+    /// Test for checking if invalid input is reported
+    /// with its position and lexing continues after it
+    #[test]
+    fn test_errors() {
+        let mut lexer = Lexer::from_string("x $ 1\n² ~", Some("errors.nx"));
+        let mut toks = Vec::new();
+        let mut errors = Vec::new();
+        loop {
+            match lexer.tokenize() {
+                Ok(tok) if tok.tok == Token::Eof => break,
+                Ok(tok) => toks.push(tok.tok),
+                Err(err) => errors.push(err),
+            }
+        }
+        assert_eq!(
+            vec![
+                Token::Ident(String::from("x")),
+                Token::Literal(Literal::Int(1)),
+                Token::Eol,
+            ],
+            toks
+        );
+        let errors: Vec<_> = errors
+            .into_iter()
+            .map(|err| (err.kind, err.span.line, err.span.col))
+            .collect();
+        assert_eq!(
+            vec![
+                (LexErrorKind::InvalidSymbol('$'), 1, 3),
+                (LexErrorKind::InvalidNumber(String::from("²")), 2, 1),
+                (LexErrorKind::InvalidSymbol('~'), 2, 3),
+            ],
+            errors
+        );
+    }
+
+    /// Test for checking if strings with embedded expressions
+    /// are split into their parts and if the embedded tokens
+    /// are located in the source of the string
+    #[test]
+    fn test_interpolation() {
+        let mut lexer = Lexer::from_string("\"Hi {name}, {a + 1}!\"", None);
+        let tok = |tok, start, end| spanned(tok, ANONYMOUS_SOURCE, start, end, (1, start + 1));
+        let expected = HashMap::from([
+            (
+                3..9,
+                vec![
+                    tok(Token::Ident(String::from("name")), 5, 9),
+                    tok(Token::Eof, 9, 9),
+                ],
+            ),
+            (
+                11..18,
+                vec![
+                    tok(Token::Ident(String::from("a")), 13, 14),
+                    tok(Token::Operator(Operator::Plus), 15, 16),
+                    tok(Token::Literal(Literal::Int(1)), 17, 18),
+                    tok(Token::Eof, 18, 18),
+                ],
+            ),
+        ]);
+        assert_eq!(
+            Token::Literal(Literal::Interpolated((
+                "Hi {name}, {a + 1}!".chars().collect(),
+                Some(expected)
+            ))),
+            lexer.tokenize().unwrap().tok
+        );
+    }
+
+    /// Test for checking if escape sequences, raw strings and
+    /// multi line strings work and invalid strings are reported
+    #[test]
+    fn test_string_kinds() {
+        let mut lexer = get_lexer("strings");
+        let file = "tests/lexer/strings.nx";
+        let name = HashMap::from([(
+            25..31,
+            vec![
+                spanned(Token::Ident(String::from("name")), file, 86, 90, (4, 16)),
+                spanned(Token::Eof, file, 90, 90, (4, 20)),
+            ],
+        )]);
+        let expected = [
+            Ok(Token::Literal(Literal::Str(String::from(
+                "tab\tquote\" brace{ unicode\u{1F980}",
+            )))),
+            Ok(Token::Eol),
+            Ok(Token::Literal(Literal::Str(String::from(
+                "C:\\path\\{raw}",
+            )))),
+            Ok(Token::Eol),
+            Ok(Token::Literal(Literal::Interpolated((
+                "first line\nsecond \"line\" {name}".chars().collect(),
+                Some(name),
+            )))),
+            Ok(Token::Eol),
+            Err(LexErrorKind::UnterminatedString),
+            Ok(Token::Eol),
+            Err(LexErrorKind::InvalidEscape('q')),
+            Ok(Token::Ident(String::from("next"))),
+            Ok(Token::Eol),
+            Ok(Token::Eof),
+        ];
+        for expect in expected {
+            let tok = lexer.tokenize().map(|tok| tok.tok).map_err(|err| err.kind);
+            assert_eq!(expect, tok);
+        }
+    }
+
+    /// Test for checking if prefixed integers, exponents,
+    /// integers that do not fit into a float and the
+    /// limits of integer literals work
+    #[test]
+    fn test_numbers() {
+        let mut lexer = get_lexer("numbers");
+        let expected = [
+            Ok(Token::Literal(Literal::Int(0xFFFF))),
+            Ok(Token::Literal(Literal::Int(0o17))),
+            Ok(Token::Literal(Literal::Int(0b1010_0101))),
+            Ok(Token::Eol),
+            Ok(Token::Literal(Literal::Num(1e-9))),
+            Ok(Token::Literal(Literal::Num(2.5E3))),
+            Ok(Token::Literal(Literal::Num(7e2))),
+            Ok(Token::Eol),
+            Ok(Token::Literal(Literal::Int(9_007_199_254_740_993))),
+            Ok(Token::Eol),
+            Ok(Token::Literal(Literal::Int(0))),
+            Ok(Token::Range),
+            Ok(Token::Literal(Literal::Int(10))),
+            Ok(Token::Eol),
+            Ok(Token::Literal(Literal::Int(-1))),
+            Ok(Token::Literal(Literal::Int(i64::MIN))),
+            Ok(Token::Literal(Literal::Int(i64::MAX))),
+            Ok(Token::Eol),
+            Err(LexErrorKind::InvalidNumber(String::from("0x"))),
+            Err(LexErrorKind::InvalidNumber(String::from("0b102"))),
+            Err(LexErrorKind::InvalidNumber(String::from(
+                "99999999999999999999",
+            ))),
+            Err(LexErrorKind::InvalidNumber(String::from(
+                "0x1_0000_0000_0000_0000",
+            ))),
+            Err(LexErrorKind::InvalidNumber(String::from(
+                "9223372036854775808",
+            ))),
+        ];
+        for expect in expected {
+            let tok = lexer.tokenize().map(|tok| tok.tok).map_err(|err| err.kind);
+            assert_eq!(expect, tok);
+        }
+    }
+
+    /// Test for checking if the source can be rebuilt
+    /// from the tokens and their trivia
+    #[test]
+    fn test_trivia() {
+        let source = "# header\nx  :=\t1 # encased # + 2\n\n  print(x) # trailing";
+        let mut lexer = Lexer::from_string(source, None);
+        lexer.set_keep_trivia(true);
+
+        let mut toks = Vec::new();
+        loop {
+            let tok = lexer.tokenize().unwrap();
+            let is_eof = tok.tok == Token::Eof;
+            toks.push(tok);
+            if is_eof {
+                break;
+            }
+        }
+
+        let rebuilt: String = toks
+            .iter()
+            .flat_map(|tok| {
+                tok.trivia
+                    .iter()
+                    .map(|trivia| trivia.text.as_str())
+                    .chain([&lexer.source()[tok.span.range()]])
+            })
+            .collect();
+        assert_eq!(source, rebuilt);
+
+        let kinds: Vec<_> = toks[0].trivia.iter().map(|trivia| trivia.kind).collect();
+        assert_eq!(vec![TriviaKind::Comment, TriviaKind::Newline], kinds);
+        assert_eq!("# encased #", toks[3].trivia[1].text);
+
+        // Trivia in front of an error belongs to its illegal token
+        let source = "x  $ y";
+        let mut lexer = Lexer::from_string(source, None);
+        lexer.set_keep_trivia(true);
+        let rebuilt: String = lexer
+            .map(|tok| tok.unwrap_or_else(|err| err.to_token()))
+            .flat_map(|tok| {
+                tok.trivia
+                    .into_iter()
+                    .map(|trivia| trivia.text)
+                    .chain([source[tok.span.range()].to_string()])
+            })
+            .collect();
+        assert_eq!(source, rebuilt);
+    }
+
+    /// Test for checking if the lexer can be used
+    /// as an iterator that ends after `Eof`
+    #[test]
+    fn test_iterator() {
+        let mut lexer = Lexer::from_string("x # comment # $ 1", None).peekable();
+        assert_eq!(
+            Some(&Token::Ident(String::from("x"))),
+            lexer
+                .peek()
+                .and_then(|tok| tok.as_ref().ok())
+                .map(|tok| &tok.tok)
+        );
+        let toks: Vec<_> = lexer
+            .map(|tok| tok.map(|tok| tok.tok).map_err(|err| err.kind))
+            .collect();
+        assert_eq!(
+            vec![
+                Ok(Token::Ident(String::from("x"))),
+                Err(LexErrorKind::InvalidSymbol('$')),
+                Ok(Token::Literal(Literal::Int(1))),
+                Ok(Token::Eof),
+            ],
+            toks
+        );
+    }
+
+    /// Test for checking if sources can be read
+    /// from memory instead of the filesystem
+    #[test]
+    fn test_memory_provider() {
+        let mut provider = MemoryProvider::new();
+        provider.insert("scripts/main.nx", "x :: 1");
+        assert!(provider.exists("scripts/main.nx".as_ref()));
+        assert!(!provider.exists("scripts/other.nx".as_ref()));
+
+        let mut lexer =
+            Lexer::from_provider(&provider, "scripts/main.nx").expect("Failed to read source");
+        assert_eq!("scripts/main.nx", lexer.file_name());
+        let tok = lexer.tokenize().expect("Failed to tokenize");
+        assert_eq!(Token::Ident(String::from("x")), tok.tok);
+
+        let err = Lexer::from_provider(&provider, "scripts/other.nx")
+            .err()
+            .expect("Read missing source");
+        assert_eq!(std::io::ErrorKind::NotFound, err.kind());
+    }
+
+    fn spanned(
+        tok: Token,
+        file: &str,
+        start: usize,
+        end: usize,
+        (line, col): (usize, usize),
+    ) -> SpannedToken {
+        SpannedToken {
+            tok,
+            span: Span {
+                file: file.into(),
+                start,
+                end,
+                line,
+                col,
+            },
+            trivia: Vec::new(),
+        }
+    }
+
     fn get_lexer(test: &str) -> Lexer {
         Lexer::new(&format!("tests/lexer/{}.nx", test)).expect("Failed to open file")
     }
@@ -1,6 +1,65 @@
-use std::{fmt::Display, collections::HashMap, ops::Range};
+use std::{collections::HashMap, fmt::Display, ops::Range, rc::Rc};
 
-pub type StringRef = (Vec<char>, Option<HashMap<Range<usize>, Vec<Token>>>);
+pub type StringRef = (Vec<char>, Option<HashMap<Range<usize>, Vec<SpannedToken>>>);
+
+/// A token together with the location
+/// it was found at in the source
+#[derive(Debug, PartialEq, Clone)]
+pub struct SpannedToken {
+    pub tok: Token,
+    pub span: Span,
+    /// Whitespace and comments in front of the token.
+    /// Only filled if the lexer keeps trivia
+    pub trivia: Vec<Trivia>,
+}
+
+/// Source that has no meaning for the program
+#[derive(Debug, PartialEq, Clone)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub text: String,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TriviaKind {
+    Whitespace,
+    /// Newline that ends an all-line comment on its own line
+    Newline,
+    Comment,
+}
+
+/// Location of a token in a source file
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Span {
+    /// Name of the file the token was found in
+    pub file: Rc<str>,
+    /// Byte offset of the first char of the token
+    pub start: usize,
+    /// Byte offset after the last char of the token
+    pub end: usize,
+    /// Line of the first char, starting at 1
+    pub line: usize,
+    /// Column of the first char, starting at 1
+    pub col: usize,
+}
+
+impl Span {
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    /// Move a span of a source that is embedded into
+    /// another one to where the embedded source starts
+    pub(crate) fn shift(&mut self, (start, line, col): (usize, usize, usize)) {
+        if self.line == 1 {
+            self.col += col - 1;
+        }
+        self.line += line - 1;
+        self.start += start;
+        self.end += start;
+    }
+}
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Token {
@@ -18,22 +77,38 @@ pub enum Token {
 
     And,
     Or,
+    As,
+    In,
     Operator(Operator),
+    /// Operator combined with an assignment like `+=`
+    OpAssign(Operator),
 
     Break,
+    Continue,
     Return,
     Local,
 
     Dot,
+    /// Safe access on a value that may be none: `?.`
+    SafeDot,
+    Range,
+    /// Rest of a list pattern: `...`
+    Ellipsis,
     Comma,
     Colon,
     QuestionMark,
     ExclamMark,
+    At,
     Arrow,
+    /// Separates the case of a when arm from its result: `->`
+    ThinArrow,
     Assign,
 
     Literal(Literal),
     Ident(String),
+    None,
+    /// Input that could not be tokenized
+    Illegal(String),
 
     LParent,
     RParent,
@@ -52,6 +127,10 @@ pub enum Token {
 #[derive(Debug, PartialEq, Clone)]
 pub enum Literal {
     Str(String),
+    /// A string with embedded expressions like `"Hello, {name}"`
+    Interpolated(StringRef),
+    Int(i64),
+    /// A floating point number
     Num(f64),
     Bool(bool),
 }
@@ -68,6 +147,7 @@ pub enum Operator {
     Minus,
     Asterisk,
     Slash,
+    Percent,
 }
 
 impl Display for Literal {
@@ -77,6 +157,8 @@ impl Display for Literal {
             "{}",
             match self {
                 Literal::Str(str) => str.to_string(),
+                Literal::Interpolated((chars, _)) => chars.iter().collect(),
+                Literal::Int(int) => int.to_string(),
                 Literal::Num(num) => num.to_string(),
                 Literal::Bool(bool) => bool.to_string(),
             }
@@ -100,6 +182,7 @@ impl Display for Operator {
                 Operator::Minus => "-",
                 Operator::Asterisk => "*",
                 Operator::Slash => "/",
+                Operator::Percent => "%",
             }
         )
     }
@@ -110,7 +193,7 @@ impl Display for Token {
         let lit: String = match self {
             Token::Use => "use".into(),
             Token::Enum => "enum".into(),
-            Token::Struct => "struct".into(),
+            Token::Struct => "container".into(),
             Token::Func => "func".into(),
             Token::Loop => "loop".into(),
             Token::If => "if".into(),
@@ -119,11 +202,19 @@ impl Display for Token {
             Token::Const => "const".into(),
             Token::And => "and".into(),
             Token::Or => "or".into(),
+            Token::As => "as".into(),
+            Token::In => "in".into(),
+            Token::None => "none".into(),
             Token::Break => "break".into(),
             Token::Return => "return".into(),
+            Token::Continue => "continue".into(),
             Token::Local => "local".into(),
             Token::Literal(lit) => lit.to_string(),
             Token::Dot => ".".into(),
+            Token::SafeDot => "?.".into(),
+            Token::Range => "..".into(),
+            Token::Ellipsis => "...".into(),
+            Token::At => "@".into(),
             Token::Comma => ",".into(),
             Token::Colon => ":".into(),
             Token::QuestionMark => "?".into(),
@@ -131,7 +222,9 @@ impl Display for Token {
             Token::Eol => "Eol".into(),
             Token::Eof => "Eof".into(),
             Token::Operator(op) => op.to_string(),
+            Token::OpAssign(op) => format!("{}=", op),
             Token::Arrow => "=>".into(),
+            Token::ThinArrow => "->".into(),
             Token::LParent => "(".into(),
             Token::RParent => ")".into(),
             Token::LSquare => "[".into(),
@@ -141,9 +234,16 @@ impl Display for Token {
             Token::Assign => "=".into(),
             Token::Else => "else".into(),
             Token::Ident(ident) => ident.into(),
+            Token::Illegal(illegal) => illegal.into(),
             Token::ConstAssign => "::".into(),
             Token::VarAssign => ":=".into(),
         };
         write!(f, "{}", lit)
     }
 }
+
+impl Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.col)
+    }
+}
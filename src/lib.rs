@@ -1,4 +1,6 @@
+#![allow(clippy::module_inception, clippy::result_large_err)]
+
+pub mod evaluator;
 pub mod lexer;
 pub mod parser;
-pub mod evaluator;
-pub mod util;
\ No newline at end of file
+pub mod util;
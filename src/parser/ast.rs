@@ -1,11 +1,21 @@
-
 use std::fmt::Display;
 
+use super::Precedence;
+
+/// Root of the syntax tree of a single source file
+#[derive(Debug, PartialEq, Clone)]
+pub struct Program {
+    pub stmts: Vec<Statement>,
+    /// Name of the file the program was parsed from
+    pub source_id: String,
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Statement {
     Variable(VarStmt),
     Return(ReturnStmt),
     Break(BreakStmt),
+    Continue(ContinueStmt),
     Local(LocalStmt),
     Use(UseStmt),
     Expression(Expression),
@@ -15,9 +25,11 @@ pub enum Statement {
 pub enum Expression {
     Ident(Ident),
     Literal(Literal),
+    Interpolated(InterpolatedExpr),
     Prefix(PrefixExpr),
     Infix(InfixExpr),
     Index(IndexExpr),
+    Field(FieldExpr),
     Call(CallExpr),
     List(ListExpr),
     None,
@@ -28,12 +40,15 @@ pub enum Expression {
     Func(FuncExpr),
     Annotation(AnnotationExpr),
     Struct(StructExpr),
+    Instance(InstanceExpr),
     Enum(EnumExpr),
 }
 
 #[derive(Debug, PartialEq, PartialOrd, Clone)]
 pub enum Literal {
     Str(String),
+    Int(i64),
+    /// A floating point number
     Num(f64),
     Bool(bool),
 }
@@ -45,6 +60,7 @@ impl Display for Literal {
             "{}",
             match self {
                 Literal::Str(str) => str.to_owned(),
+                Literal::Int(int) => int.to_string(),
                 Literal::Num(num) => num.to_string(),
                 Literal::Bool(bool) => bool.to_string(),
             }
@@ -76,6 +92,13 @@ pub struct ReturnStmt {
 #[derive(Debug, PartialEq, Clone)]
 pub struct BreakStmt {
     pub label: Option<Ident>,
+    /// The value the loop evaluates to
+    pub val: Option<Expression>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ContinueStmt {
+    pub label: Option<Ident>,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -85,8 +108,26 @@ pub struct LocalStmt {
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct UseStmt {
-    // TODO: Import paths
-    pub import: Ident,
+    /// The module path like `a.b.c`
+    pub path: Vec<Ident>,
+    /// Name the module is bound to instead of
+    /// the last part of the path
+    pub alias: Option<Ident>,
+    /// Bindings that are imported directly
+    /// instead of the whole module
+    pub items: Option<Vec<Ident>>,
+}
+
+/// A string literal with embedded expressions
+#[derive(Debug, PartialEq, Clone)]
+pub struct InterpolatedExpr {
+    pub parts: Vec<StrPart>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum StrPart {
+    Lit(String),
+    Expr(Expression),
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -105,7 +146,14 @@ pub struct InfixExpr {
 #[derive(Debug, PartialEq, Clone)]
 pub struct IndexExpr {
     pub list: Box<Expression>,
-    pub pos: usize,
+    pub index: Box<Expression>,
+}
+
+/// Access a field of a value: `Color.Red`
+#[derive(Debug, PartialEq, Clone)]
+pub struct FieldExpr {
+    pub val: Box<Expression>,
+    pub field: Ident,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -131,6 +179,9 @@ pub struct IfExpr {
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct LoopExpr {
+    /// Name that break and continue
+    /// statements can refer to
+    pub label: Option<Ident>,
     pub _type: LoopType,
     pub cond: Option<Box<Expression>>,
     pub block: BlockStmt,
@@ -161,9 +212,22 @@ pub struct StructExpr {
     pub fields: Vec<OptionallyTypedIdent>,
 }
 
+/// Construction of a container like `Point { x: 1, y }`
+#[derive(Debug, PartialEq, Clone)]
+pub struct InstanceExpr {
+    pub name: Ident,
+    pub fields: Vec<FieldInit>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct FieldInit {
+    pub field: Ident,
+    pub val: Expression,
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct EnumExpr {
-    pub consts: Ident,
+    pub variants: Vec<Ident>,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -174,11 +238,82 @@ pub struct BlockStmt {
 #[derive(Debug, PartialEq, Clone)]
 pub struct CaseStmt {
     pub _type: CaseType,
-    /// This should be Some(...) if the _type is Regular and
-    /// the WhenExpr.comp_val is Some(...) as well
-    pub comp_cond: Option<Box<Expression>>,
-    /// This should be None unless the WhenExpr.comp_val is None
-    pub comp_val: Option<Box<Expression>>,
+    /// Matched against WhenExpr.comp_val. None for else
+    /// cases and if there is no comp_val
+    pub pattern: Option<Pattern>,
+    /// Boolean guard that is used if there
+    /// is no comp_val. None for else cases
+    pub cond: Option<Box<Expression>>,
+    pub result: CaseResult,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum CaseResult {
+    Block(BlockStmt),
+    Expr(Box<Expression>),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Pattern {
+    /// Matches values that are equal to a literal
+    /// or the result of a grouped expression
+    Value(Expression),
+    /// `1..10`
+    Range(RangePattern),
+    None,
+    /// Matches any value and binds it to the name
+    Binding(Ident),
+    /// `_` matches any value without binding it
+    Wildcard,
+    /// `[first, ...rest]`
+    List(ListPattern),
+    /// `Point { x, y: 0 }`
+    Struct(StructPattern),
+    /// `Color.Red`
+    Variant(VariantPattern),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct RangePattern {
+    pub start: Box<Expression>,
+    /// The end is exclusive
+    pub end: Box<Expression>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ListPattern {
+    /// Patterns before the rest
+    pub head: Vec<Pattern>,
+    pub rest: Option<RestPattern>,
+    /// Patterns after the rest
+    pub tail: Vec<Pattern>,
+}
+
+/// Matches all items of a list that are
+/// not matched by the other patterns
+#[derive(Debug, PartialEq, Clone)]
+pub struct RestPattern {
+    pub binding: Option<Ident>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct StructPattern {
+    pub name: Ident,
+    pub fields: Vec<FieldPattern>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct FieldPattern {
+    pub field: Ident,
+    /// Binding to the name of the field if
+    /// there is no explicit pattern
+    pub pattern: Pattern,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct VariantPattern {
+    pub parent: Ident,
+    pub variant: Ident,
 }
 
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -194,6 +329,7 @@ pub enum InfixOp {
     Sub,
     Mul,
     Div,
+    Mod,
     Eq,
     NEq,
     GT,
@@ -203,7 +339,50 @@ pub enum InfixOp {
     As,
     In,
     Range,
+    And,
+    Or,
     Assign,
+    AddAssign,
+    SubAssign,
+    MulAssign,
+    DivAssign,
+    ModAssign,
+}
+
+impl InfixOp {
+    /// The arithmetic operator of a compound assignment like `+=`
+    pub fn compound_op(&self) -> Option<InfixOp> {
+        match self {
+            InfixOp::AddAssign => Some(InfixOp::Add),
+            InfixOp::SubAssign => Some(InfixOp::Sub),
+            InfixOp::MulAssign => Some(InfixOp::Mul),
+            InfixOp::DivAssign => Some(InfixOp::Div),
+            InfixOp::ModAssign => Some(InfixOp::Mod),
+            _ => None,
+        }
+    }
+
+    /// Precedence the operator is parsed with
+    fn precedence(&self) -> Precedence {
+        match self {
+            InfixOp::Add | InfixOp::Sub => Precedence::Sum,
+            InfixOp::Mul | InfixOp::Div | InfixOp::Mod => Precedence::Product,
+            InfixOp::Eq | InfixOp::NEq => Precedence::Equals,
+            InfixOp::GT | InfixOp::LT => Precedence::LessGreater,
+            InfixOp::GTEq | InfixOp::LTEq => Precedence::LessGreaterOrEqual,
+            InfixOp::As => Precedence::Conversion,
+            InfixOp::In => Precedence::Contains,
+            InfixOp::Range => Precedence::Range,
+            InfixOp::And => Precedence::And,
+            InfixOp::Or => Precedence::Or,
+            InfixOp::Assign
+            | InfixOp::AddAssign
+            | InfixOp::SubAssign
+            | InfixOp::MulAssign
+            | InfixOp::DivAssign
+            | InfixOp::ModAssign => Precedence::Assign,
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -234,6 +413,14 @@ impl Display for Ident {
     }
 }
 
+impl Display for Program {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.stmts
+            .iter()
+            .try_for_each(|stmt| writeln!(f, "{}", stmt))
+    }
+}
+
 impl Display for Statement {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -249,25 +436,32 @@ impl Display for Statement {
                     var.name,
                     var.val,
                 ),
-                Statement::Return(ret) => todo!(),
-                Statement::Break(br) => todo!(),
-                Statement::Local(lcl) => todo!(),
-                Statement::Use(_use) => todo!(),
+                Statement::Return(ret) => match &ret.val {
+                    Some(val) => format!("return {}", val),
+                    None => "return".into(),
+                },
+                Statement::Break(br) => {
+                    let mut buf = String::from("break");
+                    if let Some(label) = &br.label {
+                        buf.push_str(&format!(" {}", label));
+                    }
+                    if let Some(val) = &br.val {
+                        buf.push_str(&format!(" {}", val));
+                    }
+                    buf
+                }
+                Statement::Continue(cont) => match &cont.label {
+                    Some(label) => format!("continue {}", label),
+                    None => "continue".into(),
+                },
+                Statement::Local(lcl) => format!("local {}", lcl.val),
+                Statement::Use(_use) => _use.to_string(),
                 Statement::Expression(expr) => expr.to_string(),
             }
         )
     }
 }
 
-impl Display for OptionallyTypedIdent {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}{}", self.ident, match &self._type {
-            Some(_type) => format!(": {}", _type),
-            None => "".into()
-        })
-    }
-}
-
 impl Display for Expression {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -276,25 +470,36 @@ impl Display for Expression {
             match self {
                 Expression::Ident(ident) => ident.to_string(),
                 Expression::Literal(lit) => lit.to_string(),
+                Expression::Interpolated(interpolated) => interpolated.to_string(),
                 Expression::Prefix(prefix) => prefix.to_string(),
                 Expression::Infix(infix) => infix.to_string(),
-                Expression::Index(_) => todo!(),
-                //Expression::Call(call) => call.to_string(),
-                Expression::List(_) => todo!(),
+                Expression::Index(index) => index.to_string(),
+                Expression::Field(field) => field.to_string(),
+                Expression::Call(call) => call.to_string(),
+                Expression::List(list) => list.to_string(),
                 Expression::None => "none".into(),
-                //Expression::If(_if) => _if.to_string(),
-                //Expression::Loop(_loop) => _loop.to_string(),
-                Expression::When(_) => todo!(),
-                //Expression::Func(func) => func.to_string(),
-                Expression::Annotation(_) => todo!(),
-                Expression::Struct(_) => todo!(),
-                Expression::Enum(_) => todo!(),
-                _ => todo!(),
+                Expression::If(_if) => _if.to_string(),
+                Expression::Loop(_loop) => _loop.to_string(),
+                Expression::When(when) => when.to_string(),
+                Expression::Func(func) => func.to_string(),
+                Expression::Annotation(annotation) => format!("@{}", annotation.name),
+                Expression::Struct(_struct) => _struct.to_string(),
+                Expression::Instance(instance) => instance.to_string(),
+                Expression::Enum(_enum) => _enum.to_string(),
             }
         )
     }
 }
 
+impl Display for InterpolatedExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.parts.iter().try_for_each(|part| match part {
+            StrPart::Lit(lit) => write!(f, "{}", lit),
+            StrPart::Expr(expr) => write!(f, "{{{}}}", expr),
+        })
+    }
+}
+
 impl Display for PrefixExpr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -305,35 +510,252 @@ impl Display for PrefixExpr {
                 PrefixOp::Neg => "-",
                 PrefixOp::Not => "!",
             },
-            self.val
+            operand(&self.val, |val| val <= Precedence::Prefix)
         )
     }
 }
 
+impl Display for UseStmt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let path: Vec<_> = self.path.iter().map(|part| part.to_string()).collect();
+        write!(f, "use {}", path.join("."))?;
+        if let Some(items) = &self.items {
+            let items: Vec<_> = items.iter().map(|item| item.to_string()).collect();
+            write!(f, ".{{{}}}", items.join(", "))?;
+        }
+        if let Some(alias) = &self.alias {
+            write!(f, " as {}", alias)?;
+        }
+        Ok(())
+    }
+}
+
+/// Operands that would be parsed differently without
+/// parentheses are displayed inside of them
+fn operand(expr: &Expression, needs_parens: impl Fn(Precedence) -> bool) -> String {
+    let prec = match expr {
+        Expression::Infix(infix) => infix.op.precedence(),
+        Expression::Prefix(_) => Precedence::Prefix,
+        _ => return expr.to_string(),
+    };
+    match needs_parens(prec) {
+        true => format!("({})", expr),
+        false => expr.to_string(),
+    }
+}
+
 impl Display for InfixExpr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{} {} {}",
-            self.left,
-            match self.op {
-                InfixOp::Add => "+",
-                InfixOp::Sub => "-",
-                InfixOp::Mul => "*",
-                InfixOp::Div => "/",
-                InfixOp::Eq => "==",
-                InfixOp::NEq => "!=",
-                InfixOp::GT => ">",
-                InfixOp::LT => "<",
-                InfixOp::GTEq => ">=",
-                InfixOp::LTEq => "<=",
-                InfixOp::As => "as",
-                InfixOp::In => "in",
-                InfixOp::Range => return write!(f, "{}..{}", self.left, self.right),
-                InfixOp::Assign => "=",
-            },
-            self.right
-        )
+        let prec = self.op.precedence();
+        let left = operand(&self.left, |left| left < prec);
+        // Assignments are right associative, all other operators left associative
+        let right = match prec {
+            Precedence::Assign => operand(&self.right, |right| right < prec),
+            _ => operand(&self.right, |right| right <= prec),
+        };
+        match self.op {
+            InfixOp::Range => write!(f, "{}..{}", left, right),
+            op => write!(f, "{} {} {}", left, op, right),
+        }
+    }
+}
+
+impl Display for InfixOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            InfixOp::Add => "+",
+            InfixOp::Sub => "-",
+            InfixOp::Mul => "*",
+            InfixOp::Div => "/",
+            InfixOp::Mod => "%",
+            InfixOp::Eq => "==",
+            InfixOp::NEq => "!=",
+            InfixOp::GT => ">",
+            InfixOp::LT => "<",
+            InfixOp::GTEq => ">=",
+            InfixOp::LTEq => "<=",
+            InfixOp::As => "as",
+            InfixOp::In => "in",
+            InfixOp::Range => "..",
+            InfixOp::And => "and",
+            InfixOp::Or => "or",
+            InfixOp::Assign => "=",
+            InfixOp::AddAssign => "+=",
+            InfixOp::SubAssign => "-=",
+            InfixOp::MulAssign => "*=",
+            InfixOp::DivAssign => "/=",
+            InfixOp::ModAssign => "%=",
+        })
+    }
+}
+
+impl Display for IndexExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}[{}]", self.list, self.index)
+    }
+}
+
+impl Display for FieldExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}", self.val, self.field)
+    }
+}
+
+impl Display for StructExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.fields.is_empty() {
+            return write!(f, "container");
+        }
+        let fields: Vec<_> = self.fields.iter().map(|field| field.to_string()).collect();
+        write!(f, "container {{ {} }}", fields.join(", "))
+    }
+}
+
+impl Display for InstanceExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let fields: Vec<_> = self
+            .fields
+            .iter()
+            .map(|init| match &init.val {
+                Expression::Ident(ident) if *ident == init.field => init.field.to_string(),
+                val => format!("{}: {}", init.field, val),
+            })
+            .collect();
+        write!(f, "{} {{ {} }}", self.name, fields.join(", "))
+    }
+}
+
+impl Display for EnumExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let variants: Vec<_> = self.variants.iter().map(|var| var.to_string()).collect();
+        write!(f, "enum {{ {} }}", variants.join(", "))
+    }
+}
+
+impl Display for ListExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let items: Vec<_> = self.list.iter().map(|item| item.to_string()).collect();
+        write!(f, "[{}]", items.join(", "))
+    }
+}
+
+impl Display for LoopExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(label) = &self.label {
+            write!(f, "{}: ", label)?;
+        }
+        match (self._type, &self.cond) {
+            (LoopType::Else, _) => write!(f, "{}", self.block)?,
+            (_, Some(cond)) => write!(f, "loop {} {}", cond, self.block)?,
+            (_, None) => write!(f, "loop {}", self.block)?,
+        }
+        match &self.alt {
+            Some(alt) => write!(f, " else {}", alt),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Display for CallExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let args: Vec<_> = self.args.iter().map(|arg| arg.to_string()).collect();
+        write!(f, "{}({})", self.ident, args.join(", "))
+    }
+}
+
+impl Display for IfExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.cond {
+            Some(cond) => write!(f, "if {} {}", cond, self.block)?,
+            None => write!(f, "{}", self.block)?,
+        }
+        match &self.alt {
+            Some(alt) => write!(f, " else {}", alt),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Display for FuncExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let args: Vec<_> = self.args.iter().map(|arg| arg.to_string()).collect();
+        write!(f, "func({})", args.join(", "))?;
+        if let Some(ret_type) = &self.ret_type {
+            write!(f, ": {}", ret_type)?;
+        }
+        write!(f, " {}", self.block)
+    }
+}
+
+impl Display for WhenExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.comp_val {
+            Some(val) => write!(f, "when {} {{", val)?,
+            None => write!(f, "when {{")?,
+        }
+        let cases: Vec<_> = self.cases.iter().map(|case| case.to_string()).collect();
+        write!(f, " {} }}", cases.join(", "))
+    }
+}
+
+impl Display for CaseStmt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.pattern, &self.cond) {
+            (Some(pattern), _) => write!(f, "{} -> ", pattern)?,
+            (None, Some(cond)) => write!(f, "{} -> ", cond)?,
+            (None, None) => write!(f, "else -> ")?,
+        }
+        match &self.result {
+            CaseResult::Block(block) => write!(f, "{}", block),
+            CaseResult::Expr(expr) => write!(f, "{}", expr),
+        }
+    }
+}
+
+impl Display for Pattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Pattern::Value(val) => write!(f, "{}", val),
+            Pattern::Range(range) => write!(f, "{}..{}", range.start, range.end),
+            Pattern::None => f.write_str("none"),
+            Pattern::Binding(ident) => write!(f, "{}", ident),
+            Pattern::Wildcard => f.write_str("_"),
+            Pattern::List(list) => {
+                let mut items: Vec<_> = list.head.iter().map(|item| item.to_string()).collect();
+                if let Some(rest) = &list.rest {
+                    items.push(match &rest.binding {
+                        Some(binding) => format!("...{}", binding),
+                        None => "...".into(),
+                    });
+                }
+                items.extend(list.tail.iter().map(|item| item.to_string()));
+                write!(f, "[{}]", items.join(", "))
+            }
+            Pattern::Struct(_struct) => {
+                let fields: Vec<_> = _struct
+                    .fields
+                    .iter()
+                    .map(|field| match &field.pattern {
+                        Pattern::Binding(binding) if *binding == field.field => {
+                            field.field.to_string()
+                        }
+                        pattern => format!("{}: {}", field.field, pattern),
+                    })
+                    .collect();
+                write!(f, "{} {{ {} }}", _struct.name, fields.join(", "))
+            }
+            Pattern::Variant(variant) => write!(f, "{}.{}", variant.parent, variant.variant),
+        }
+    }
+}
+
+impl Display for BlockStmt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{{")?;
+        self.stmts
+            .iter()
+            .try_for_each(|stmt| writeln!(f, "    {}", stmt))?;
+        write!(f, "}}")
     }
 }
 
@@ -1,9 +1,17 @@
 pub mod ast;
 mod tests;
 
-use std::{error::Error, fmt::Display, mem::swap};
-
-use crate::{lexer::{tokens::{Token, Operator, Literal as TkLit}, Lexer}, util};
+use std::{
+    collections::VecDeque,
+    error::Error,
+    fmt::Display,
+    mem::{replace, swap, take},
+};
+
+use crate::lexer::{
+    tokens::{Literal as TkLit, Operator, Span, SpannedToken, Token},
+    LexError, LexErrorKind, Lexer,
+};
 use ast::*;
 
 pub struct Parser<'a> {
@@ -11,6 +19,37 @@ pub struct Parser<'a> {
 
     cur_tok: Token,
     peek_tok: Token,
+
+    cur_span: Span,
+    peek_span: Span,
+
+    lex_errors: Vec<LexError>,
+    /// Errors of statements that parsing recovered from
+    errors: Vec<ParseError>,
+    /// Brackets that are currently open. Decides if
+    /// newlines end the current statement and where
+    /// a statement ends after an error
+    nesting: Vec<Nesting>,
+    /// Labels of the loops that are currently parsed
+    labels: Vec<Ident>,
+    /// Tokens that are consumed before
+    /// new ones are requested from the lexer
+    queued: VecDeque<SpannedToken>,
+    /// Nesting depth of the condition that is currently parsed.
+    /// Containers can only be constructed in brackets inside of it
+    cond_depth: Option<usize>,
+    /// Whether newlines in front of the peek token were skipped
+    peek_after_eol: bool,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Nesting {
+    /// Inside of `()`, `[]` or a literal in `{}`.
+    /// Newlines are ignored
+    Group,
+    /// Inside of a block of statements.
+    /// Newlines separate the statements
+    Block,
 }
 
 #[repr(u8)]
@@ -20,6 +59,14 @@ enum Precedence {
     Lowest,
     /// Assign new value to variable
     Assign,
+    /// Logical or
+    ///
+    /// `a or b`
+    Or,
+    /// Logical and
+    ///
+    /// `a and b`
+    And,
     /// Check if i is in list/range
     ///
     /// `i in 0..10`
@@ -55,48 +102,137 @@ enum Precedence {
 
 impl<'a> Parser<'a> {
     pub fn new(lexer: &'a mut Lexer) -> Self {
-        let cur_tok = util::get_next_tok(lexer);
-        let peek_tok = util::get_next_tok(lexer);
+        let mut lex_errors = Vec::new();
+        let cur = Self::lex_next(lexer, &mut lex_errors);
+        let peek = Self::lex_next(lexer, &mut lex_errors);
         Self {
             lexer,
-            cur_tok,
-            peek_tok,
+            cur_tok: cur.tok,
+            peek_tok: peek.tok,
+            cur_span: cur.span,
+            peek_span: peek.span,
+            lex_errors,
+            errors: Vec::new(),
+            nesting: Vec::new(),
+            labels: Vec::new(),
+            queued: VecDeque::new(),
+            cond_depth: None,
+            peek_after_eol: false,
         }
     }
 
-    pub fn parse_stmt(&mut self) -> Result<Statement, EofError> {
+    /// Errors the lexer reported so far. Each of them
+    /// was passed on to the parser as an illegal token
+    pub fn lex_errors(&self) -> &[LexError] {
+        &self.lex_errors
+    }
+
+    /// Parse every statement until the end of the file.
+    /// Parsing continues after a syntax error at the next
+    /// statement, so all errors of the file are returned at once
+    pub fn parse_program(&mut self) -> Result<Program, Vec<ParseError>> {
+        let mut stmts = Vec::new();
+
+        loop {
+            match self.cur_tok {
+                Token::Eol => {
+                    self.next_token();
+                    continue;
+                }
+                Token::Eof => break,
+                _ => (),
+            }
+            match self
+                .parse_stmt()
+                .and_then(|stmt| self.expect_stmt_end(Token::Eof).map(|_| stmt))
+            {
+                Ok(stmt) => stmts.push(stmt),
+                Err(err) => {
+                    self.labels.clear();
+                    self.cond_depth = None;
+                    self.synchronize(&err, 0);
+                    self.errors.push(err);
+                }
+            }
+            self.next_token();
+        }
+
+        let errors = take(&mut self.errors);
+        match errors.is_empty() {
+            true => Ok(Program {
+                stmts,
+                source_id: self.lexer.file_name().into(),
+            }),
+            false => Err(errors),
+        }
+    }
+
+    pub fn parse_stmt(&mut self) -> Result<Statement, ParseError> {
         Ok(match self.cur_tok {
-            Token::Use => todo!(),
-            Token::Var => self.parse_variable(false),
-            Token::Const => self.parse_variable(true),
+            Token::Use => self.parse_use_stmt()?,
+            Token::Var => self.parse_variable(false)?,
+            Token::Const => self.parse_variable(true)?,
             Token::Break => {
+                // Identifiers that are not the label of
+                // a surrounding loop are parsed as the value
                 let label = match self.peek_tok {
-                    Token::Ident(_) => {
+                    Token::Ident(ref ident)
+                        if self.labels.iter().any(|label| label.0 == *ident) =>
+                    {
+                        Some(self.expect_peek_ident()?)
+                    }
+                    _ => None,
+                };
+                let val = match self.peek_is_stmt_end() {
+                    true => None,
+                    false => {
                         self.next_token();
-                        Some(Ident(self.cur_tok.to_string()))
+                        Some(self.parse_expr(Precedence::Lowest)?)
+                    }
+                };
+                Statement::Break(BreakStmt { label, val })
+            }
+            Token::Continue => {
+                let label = match self.peek_tok {
+                    Token::Ident(_) => {
+                        let label = self.expect_peek_ident()?;
+                        if !self.labels.contains(&label) {
+                            return Err(ParseError {
+                                kind: ParseErrorKind::UnknownLabel(label.0),
+                                span: self.cur_span.clone(),
+                            });
+                        }
+                        Some(label)
                     }
                     _ => None,
                 };
-                Statement::Break(BreakStmt { label })
+                Statement::Continue(ContinueStmt { label })
             }
             Token::Return => {
-                let val = match self.peek_tok {
-                    Token::Eol => None,
-                    _ => {
+                let val = match self.peek_is_stmt_end() {
+                    true => None,
+                    false => {
                         self.next_token();
-                        Some(self.parse_expr(Precedence::Lowest))
+                        Some(self.parse_expr(Precedence::Lowest)?)
                     }
                 };
                 Statement::Return(ReturnStmt { val })
             }
             Token::Local => {
                 if self.peek_tok == Token::Local {
-                    panic!("Cannot stack multiple `local` statements")
+                    return Err(ParseError {
+                        kind: ParseErrorKind::StackedLocal,
+                        span: self.peek_span.clone(),
+                    });
                 }
                 self.next_token();
-                let stmt = self.parse_stmt().expect(
-                    "Encountered End of file instead of a statement after the local statement",
-                );
+                let stmt = match self.parse_stmt() {
+                    Err(ParseError {
+                        kind: ParseErrorKind::Eof,
+                        ..
+                    }) => return Err(self.cur_error(Expected::Statement)),
+                    other => other?,
+                };
                 Statement::Local(LocalStmt {
                     val: Box::new(stmt),
                 })
@@ -105,72 +241,81 @@ impl<'a> Parser<'a> {
                 self.next_token();
                 return self.parse_stmt();
             }
-            Token::Eof => return Err(EofError),
+            Token::Eof => {
+                return Err(ParseError {
+                    kind: ParseErrorKind::Eof,
+                    span: self.cur_span.clone(),
+                })
+            }
             _ => {
-                if let Token::Ident(_) = self.cur_tok {
-                    if self.peek_tok == Token::VarAssign {
-                        return Ok(self.parse_quick_assign());
-                    }
+                if matches!(self.cur_tok, Token::Ident(_)) && !self.cur_is_label() {
                     match self.peek_tok {
                         Token::Colon | Token::ConstAssign | Token::VarAssign => {
-                            return Ok(self.parse_quick_assign())
+                            return self.parse_quick_assign()
                         }
                         _ => (),
                     }
                 }
-                Statement::Expression(self.parse_expr(Precedence::Lowest))
+                Statement::Expression(self.parse_expr(Precedence::Lowest)?)
             }
         })
     }
 
-    fn parse_expr(&mut self, precedence: Precedence) -> Expression {
-        let prefix = self.parse_prefix();
-        if prefix.is_none() {
-            panic!("No prefix parse found for: {}", self.cur_tok)
-        }
-
-        let prefix = prefix.unwrap();
-        let mut left_expression = prefix;
+    fn parse_expr(&mut self, precedence: Precedence) -> Result<Expression, ParseError> {
+        let mut left_expression = match self.parse_prefix()? {
+            Some(expr) => expr,
+            None => return Err(self.cur_error(Expected::Expression)),
+        };
 
         while !self.peek_is_end() && precedence < self.get_precedence(&self.peek_tok) {
             self.next_token();
-            // Unwrap here might not be safe. Observe this
-            left_expression = match self.parse_infix(left_expression) {
+            left_expression = match self.parse_infix(left_expression)? {
                 Some(expr) => expr,
-                None => panic!("Invalid infix expression"),
+                None => return Err(self.cur_error(Expected::Operator)),
             };
         }
 
-        left_expression
+        Ok(left_expression)
     }
 
-    fn parse_prefix(&mut self) -> Option<Expression> {
-        Some(match self.cur_tok {
+    fn parse_prefix(&mut self) -> Result<Option<Expression>, ParseError> {
+        if self.cur_is_label() {
+            return Ok(Some(self.parse_labeled_loop()?));
+        }
+        Ok(Some(match self.cur_tok {
+            Token::Ident(_) if self.peek_tok == Token::LCurly && self.instance_allowed() => {
+                self.parse_instance_expr()?
+            }
             Token::Ident(_) => Expression::Ident(Ident(self.cur_tok.to_string())),
             Token::Literal(TkLit::Bool(ref bool)) => Expression::Literal(Literal::Bool(*bool)),
+            Token::Literal(TkLit::Int(ref lit)) => Expression::Literal(Literal::Int(*lit)),
             Token::Literal(TkLit::Num(ref lit)) => Expression::Literal(Literal::Num(*lit)),
-            Token::Literal(TkLit::Str(_)) => self.parse_str_lit(),
-            Token::LSquare => self.parse_list_lit(),
+            Token::Literal(TkLit::Str(_)) | Token::Literal(TkLit::Interpolated(_)) => {
+                self.parse_str_lit()?
+            }
+            Token::LSquare => self.parse_list_lit()?,
             // TODO: add hashes
             /*
             Token::LCURLY => self.parse_hash_literal(),
             */
-            // Token::NONE => Expression::NONE(NoneLiteral),
-            Token::LParent => self.parse_grouped_expr(),
-            Token::Func => self.parse_func_expr(),
-            Token::If => self.parse_if_expr(IfType::If),
-            Token::Loop => self.parse_loop_expr(),
-            Token::When => self.parse_when_expr(),
+            Token::None => Expression::None,
+            Token::LParent => self.parse_grouped_expr()?,
+            Token::Func => self.parse_func_expr()?,
+            Token::Enum => self.parse_enum_expr()?,
+            Token::Struct => self.parse_struct_expr()?,
+            Token::If => Expression::If(self.parse_if_expr(IfType::If)?),
+            Token::Loop => self.parse_loop_expr()?,
+            Token::When => self.parse_when_expr()?,
             Token::ExclamMark
             | Token::Operator(Operator::Plus)
-            | Token::Operator(Operator::Minus) => self.parse_prefix_expr(),
+            | Token::Operator(Operator::Minus) => self.parse_prefix_expr()?,
             // Token::ANNOTATION => self.parse_annotation(),
-            _ => return None,
-        })
+            _ => return Ok(None),
+        }))
     }
 
-    fn parse_infix(&mut self, left: Expression) -> Option<Expression> {
-        Some(match self.cur_tok {
+    fn parse_infix(&mut self, left: Expression) -> Result<Option<Expression>, ParseError> {
+        Ok(Some(match self.cur_tok {
             Token::Operator(ref op) => match op {
                 Operator::Equals
                 | Operator::NotEquals
@@ -181,165 +326,622 @@ impl<'a> Parser<'a> {
                 | Operator::Plus
                 | Operator::Minus
                 | Operator::Asterisk
-                | Operator::Slash => self.parse_infix_expr(left),
+                | Operator::Slash
+                | Operator::Percent => self.parse_infix_expr(left)?,
             },
-            Token::LParent => self.parse_call_expr(left),
-            // Token::LSquare => self.parse_index_expr(left),
-            _ => return None,
-        })
+            Token::Range | Token::In | Token::As | Token::And | Token::Or => {
+                self.parse_infix_expr(left)?
+            }
+            Token::Assign | Token::OpAssign(_) => self.parse_assign_expr(left)?,
+            Token::LParent => self.parse_call_expr(left)?,
+            Token::LSquare => self.parse_index_expr(left)?,
+            Token::Dot => self.parse_field_expr(left)?,
+            _ => return Ok(None),
+        }))
+    }
+
+    fn parse_str_lit(&mut self) -> Result<Expression, ParseError> {
+        let (chars, embedded) = match &self.cur_tok {
+            Token::Literal(TkLit::Interpolated((chars, Some(embedded)))) => {
+                (chars.clone(), embedded.clone())
+            }
+            other => return Ok(Expression::Literal(Literal::Str(other.to_string()))),
+        };
+
+        let mut embedded: Vec<_> = embedded.into_iter().collect();
+        embedded.sort_by_key(|(range, _)| range.start);
+
+        let mut parts = Vec::new();
+        let mut pos = 0;
+        for (range, toks) in embedded {
+            if pos < range.start {
+                parts.push(StrPart::Lit(chars[pos..range.start].iter().collect()));
+            }
+            parts.push(StrPart::Expr(self.parse_embedded(toks)?));
+            pos = range.end;
+        }
+        if pos < chars.len() {
+            parts.push(StrPart::Lit(chars[pos..].iter().collect()));
+        }
+        Ok(Expression::Interpolated(InterpolatedExpr { parts }))
     }
 
-    fn parse_str_lit(&mut self) -> Expression {
-        // TODO: Parse string interpolation
-        Expression::Literal(Literal::Str(self.cur_tok.to_string()))
+    /// Parse the tokens of an expression that is embedded
+    /// into the current string literal. The tokens end with
+    /// an `Eof` that parsing does not read past. The current
+    /// and peek token are the same before and after this call
+    fn parse_embedded(&mut self, toks: Vec<SpannedToken>) -> Result<Expression, ParseError> {
+        let queued = replace(&mut self.queued, toks.into());
+        let cur = (self.cur_tok.clone(), self.cur_span.clone());
+        let peek = (self.peek_tok.clone(), self.peek_span.clone());
+
+        self.next_token();
+        self.next_token();
+        let expr = self
+            .parse_expr(Precedence::Lowest)
+            .and_then(|expr| match self.peek_tok {
+                Token::Eof => Ok(expr),
+                _ => Err(self.peek_error(Expected::Token(Token::RCurly))),
+            });
+
+        self.queued = queued;
+        (self.cur_tok, self.cur_span) = cur;
+        (self.peek_tok, self.peek_span) = peek;
+        expr
     }
 
-    fn parse_list_lit(&mut self) -> Expression {
-        todo!()
+    fn parse_list_lit(&mut self) -> Result<Expression, ParseError> {
+        let list = self.parse_raw_list(Token::RSquare)?;
+        Ok(Expression::List(ListExpr { list }))
     }
 
-    fn parse_grouped_expr(&mut self) -> Expression {
-        todo!()
+    fn parse_grouped_expr(&mut self) -> Result<Expression, ParseError> {
+        self.enter(Nesting::Group);
+        self.next_token();
+        let expr = self.parse_expr(Precedence::Lowest)?;
+        self.expect_peek(Token::RParent)?;
+        self.leave();
+        self.next_token();
+        Ok(expr)
     }
 
-    fn parse_func_expr(&mut self) -> Expression {
-        self.expect_peek(Token::LParent);
+    fn parse_func_expr(&mut self) -> Result<Expression, ParseError> {
+        self.expect_peek(Token::LParent)?;
         self.next_token();
-        let args = self.parse_ident_list(Token::RParent);
+        let args = self.parse_ident_list(Token::RParent)?;
         let ret_type = match self.peek_tok {
             Token::Colon => {
                 self.next_token();
-                self.next_token();
-                Some(Ident(self.cur_tok.to_string()))
+                Some(self.expect_peek_ident()?)
             }
             Token::LCurly => None,
-            _ => panic!(),
+            _ => return Err(self.peek_error(Expected::OneOf(vec![Token::Colon, Token::LCurly]))),
         };
+        self.expect_peek(Token::LCurly)?;
         self.next_token();
-        let block = self.parse_block_stmt();
-        Expression::Func(FuncExpr {
+        let block = self.parse_block_stmt()?;
+        Ok(Expression::Func(FuncExpr {
             ret_type,
             args,
             block,
-        })
+        }))
     }
 
-    fn parse_if_expr(&mut self, _type: IfType) -> Expression {
-        match _type {
+    fn parse_if_expr(&mut self, _type: IfType) -> Result<IfExpr, ParseError> {
+        Ok(match _type {
             // Current token needs to be `if`
             IfType::If => {
                 self.next_token();
-                let cond = self.parse_expr(Precedence::Lowest);
-                self.expect_peek(Token::LCurly);
+                let cond = self.parse_cond()?;
+                self.expect_peek(Token::LCurly)?;
                 self.next_token();
-                let block = self.parse_block_stmt();
+                let block = self.parse_block_stmt()?;
                 let alt = match self.peek_tok {
                     Token::Else => {
                         self.next_token();
                         Some(Box::from(match self.peek_tok {
-                            Token::If => match self.parse_if_expr(IfType::ElseIf) {
-                                Expression::If(_if) => _if,
-                                _ => panic!("UNREACHABLE"),
-                            },
-                            Token::LCurly => match self.parse_if_expr(IfType::Else) {
-                                Expression::If(_if) => _if,
-                                _ => panic!("UNREACHABLE"),
-                            },
-                            ref other => {
-                                panic!("Exptected `block` or `if` after else, got `{other:?}`")
+                            Token::If => self.parse_if_expr(IfType::ElseIf)?,
+                            Token::LCurly => self.parse_if_expr(IfType::Else)?,
+                            _ => {
+                                return Err(self
+                                    .peek_error(Expected::OneOf(vec![Token::If, Token::LCurly])))
                             }
                         }))
                     }
                     _ => None,
                 };
-                Expression::If(IfExpr {
+                IfExpr {
                     cond: Some(Box::from(cond)),
                     block,
                     _type,
                     alt,
-                })
+                }
             }
             // Current token needs to be `else`
             IfType::ElseIf => {
                 self.next_token();
-                let _if = match self.parse_if_expr(IfType::If) {
-                    Expression::If(_if) => _if,
-                    other => panic!("Unreachable: Got {other:?} instead of if expression"),
-                };
-                Expression::If(IfExpr { _type, .._if })
+                let _if = self.parse_if_expr(IfType::If)?;
+                IfExpr { _type, .._if }
             }
             // Current token needs to be `else`
             IfType::Else => {
                 self.next_token();
-                let block = self.parse_block_stmt();
-                Expression::If(IfExpr {
+                let block = self.parse_block_stmt()?;
+                IfExpr {
                     _type,
                     cond: None,
                     block,
                     alt: None,
-                })
+                }
             }
-        }
+        })
     }
 
-    // TODO: parse else branches
-    fn parse_loop_expr(&mut self) -> Expression {
+    fn parse_loop_expr(&mut self) -> Result<Expression, ParseError> {
+        Ok(Expression::Loop(self.parse_loop(false)?))
+    }
+
+    /// Checks if the current token is the label of a loop like `outer: loop`
+    fn cur_is_label(&mut self) -> bool {
+        matches!(self.cur_tok, Token::Ident(_))
+            && self.peek_tok == Token::Colon
+            && *self.peek_second() == Token::Loop
+    }
+
+    /// Current token needs to be the label
+    fn parse_labeled_loop(&mut self) -> Result<Expression, ParseError> {
+        let label = Ident(self.cur_tok.to_string());
         self.next_token();
-        let cond = self.parse_expr(Precedence::Lowest);
-        self.expect_peek(Token::LCurly);
         self.next_token();
-        let block = self.parse_block_stmt();
-        Expression::Loop(LoopExpr {
-            _type: LoopType::While,
-            cond: Some(Box::from(cond)),
+        self.labels.push(label.clone());
+        let _loop = self.parse_loop(false)?;
+        self.labels.pop();
+        Ok(Expression::Loop(LoopExpr {
+            label: Some(label),
+            .._loop
+        }))
+    }
+
+    /// Current token needs to be `loop`. Loops without a condition run
+    /// until they are broken out of and loops with a condition like
+    /// `i in 0..10` that starts with an identifier iterate over a value
+    fn parse_loop(&mut self, is_alt: bool) -> Result<LoopExpr, ParseError> {
+        let cond = match self.peek_tok {
+            Token::LCurly => None,
+            _ => {
+                self.next_token();
+                Some(Box::from(self.parse_cond()?))
+            }
+        };
+        let is_for = matches!(
+            cond.as_deref(),
+            Some(Expression::Infix(InfixExpr {
+                op: InfixOp::In,
+                left,
+                ..
+            })) if matches!(**left, Expression::Ident(_))
+        );
+        let _type = match (is_alt, is_for) {
+            (false, false) => LoopType::While,
+            (false, true) => LoopType::For,
+            (true, false) => LoopType::ElseWhile,
+            (true, true) => LoopType::ElseFor,
+        };
+
+        self.expect_peek(Token::LCurly)?;
+        self.next_token();
+        let block = self.parse_block_stmt()?;
+
+        let alt = match self.peek_tok {
+            Token::Else => {
+                self.next_token();
+                Some(Box::from(match self.peek_tok {
+                    Token::Loop => {
+                        self.next_token();
+                        self.parse_loop(true)?
+                    }
+                    Token::LCurly => {
+                        self.next_token();
+                        LoopExpr {
+                            label: None,
+                            _type: LoopType::Else,
+                            cond: None,
+                            block: self.parse_block_stmt()?,
+                            alt: None,
+                        }
+                    }
+                    _ => {
+                        return Err(
+                            self.peek_error(Expected::OneOf(vec![Token::Loop, Token::LCurly]))
+                        )
+                    }
+                }))
+            }
+            _ => None,
+        };
+
+        Ok(LoopExpr {
+            label: None,
+            _type,
+            cond,
             block,
-            alt: None,
+            alt,
         })
     }
 
-    fn parse_when_expr(&mut self) -> Expression {
-        todo!()
+    fn parse_when_expr(&mut self) -> Result<Expression, ParseError> {
+        let comp_val = match self.peek_tok {
+            Token::LCurly => None,
+            _ => {
+                self.next_token();
+                Some(Box::from(self.parse_cond()?))
+            }
+        };
+        self.expect_peek(Token::LCurly)?;
+        self.next_token();
+
+        let mut cases = Vec::new();
+        self.enter(Nesting::Block);
+        loop {
+            match self.peek_tok {
+                Token::RCurly => break,
+                Token::Eof => return Err(self.peek_error(Expected::Token(Token::RCurly))),
+                Token::Eol => self.next_token(),
+                _ => {
+                    self.next_token();
+                    cases.push(self.parse_case_stmt(comp_val.is_some())?);
+                    match self.peek_tok {
+                        Token::Comma => self.next_token(),
+                        Token::Eol | Token::RCurly => (),
+                        _ => {
+                            return Err(
+                                self.peek_error(Expected::OneOf(vec![Token::Comma, Token::RCurly]))
+                            )
+                        }
+                    }
+                }
+            }
+        }
+        self.leave();
+        self.next_token();
+
+        Ok(Expression::When(WhenExpr { comp_val, cases }))
+    }
+
+    /// Parses a single arm of a when expression like `1 -> x`.
+    /// Arms of when expressions with a value to compare start with
+    /// a pattern, the others with a condition. The result of the
+    /// arm can be an expression or a block
+    fn parse_case_stmt(&mut self, has_comp_val: bool) -> Result<CaseStmt, ParseError> {
+        let (_type, pattern, cond) = match self.cur_tok {
+            Token::Else => (CaseType::Else, None, None),
+            _ if has_comp_val => (CaseType::Regular, Some(self.parse_pattern()?), None),
+            _ => (
+                CaseType::Regular,
+                None,
+                Some(Box::from(self.parse_expr(Precedence::Lowest)?)),
+            ),
+        };
+        self.expect_peek(Token::ThinArrow)?;
+        self.next_token();
+        self.next_token();
+        let result = match self.cur_tok {
+            Token::LCurly => CaseResult::Block(self.parse_block_stmt()?),
+            _ => CaseResult::Expr(Box::from(self.parse_expr(Precedence::Lowest)?)),
+        };
+        Ok(CaseStmt {
+            _type,
+            pattern,
+            cond,
+            result,
+        })
     }
 
-    fn parse_infix_expr(&mut self, left_expr: Expression) -> Expression {
-        let op = match self.cur_tok {
-            Token::Operator(_) => self.cur_tok_to_in_op(),
-            ref other => panic!("Missing operator, got {other} instead"),
+    /// Sets cur_tok to the last token of the pattern
+    fn parse_pattern(&mut self) -> Result<Pattern, ParseError> {
+        Ok(match self.cur_tok {
+            Token::Ident(ref ident) if ident == "_" => Pattern::Wildcard,
+            Token::Ident(ref ident) => {
+                let ident = Ident(ident.clone());
+                match self.peek_tok {
+                    Token::Dot => {
+                        self.next_token();
+                        let variant = self.expect_peek_ident()?;
+                        Pattern::Variant(VariantPattern {
+                            parent: ident,
+                            variant,
+                        })
+                    }
+                    Token::LCurly => Pattern::Struct(self.parse_struct_pattern(ident)?),
+                    _ => Pattern::Binding(ident),
+                }
+            }
+            Token::None => Pattern::None,
+            Token::LSquare => Pattern::List(self.parse_list_pattern()?),
+            Token::LParent => Pattern::Value(self.parse_grouped_expr()?),
+            Token::Literal(_) | Token::Operator(Operator::Minus) => {
+                let start = self.parse_expr(Precedence::Range)?;
+                match self.peek_tok {
+                    Token::Range => {
+                        self.next_token();
+                        self.next_token();
+                        let end = self.parse_expr(Precedence::Range)?;
+                        Pattern::Range(RangePattern {
+                            start: Box::from(start),
+                            end: Box::from(end),
+                        })
+                    }
+                    _ => Pattern::Value(start),
+                }
+            }
+            _ => return Err(self.cur_error(Expected::Pattern)),
+        })
+    }
+
+    /// First token needs to be the left square `[`
+    fn parse_list_pattern(&mut self) -> Result<ListPattern, ParseError> {
+        let mut head = Vec::new();
+        let mut rest = None;
+        let mut tail = Vec::new();
+
+        self.enter(Nesting::Group);
+        while self.peek_tok != Token::RSquare {
+            self.next_token();
+            match self.cur_tok {
+                Token::Ellipsis => {
+                    if rest.is_some() {
+                        return Err(ParseError {
+                            kind: ParseErrorKind::MultipleRest,
+                            span: self.cur_span.clone(),
+                        });
+                    }
+                    let binding = match self.peek_tok {
+                        Token::Ident(_) => Some(self.expect_peek_ident()?),
+                        _ => None,
+                    };
+                    rest = Some(RestPattern { binding });
+                }
+                _ => {
+                    let pattern = self.parse_pattern()?;
+                    match rest {
+                        Some(_) => tail.push(pattern),
+                        None => head.push(pattern),
+                    }
+                }
+            }
+            self.expect_separator(&Token::RSquare)?;
+        }
+        self.leave();
+        self.next_token();
+
+        Ok(ListPattern { head, rest, tail })
+    }
+
+    /// Current token needs to be the name of the struct
+    fn parse_struct_pattern(&mut self, name: Ident) -> Result<StructPattern, ParseError> {
+        self.next_token();
+        let mut fields = Vec::new();
+
+        self.enter(Nesting::Group);
+        while self.peek_tok != Token::RCurly {
+            let field = self.expect_peek_ident()?;
+            let pattern = match self.peek_tok {
+                Token::Colon => {
+                    self.next_token();
+                    self.next_token();
+                    self.parse_pattern()?
+                }
+                _ => Pattern::Binding(field.clone()),
+            };
+            fields.push(FieldPattern { field, pattern });
+            self.expect_separator(&Token::RCurly)?;
+        }
+        self.leave();
+        self.next_token();
+
+        Ok(StructPattern { name, fields })
+    }
+
+    /// Parses `use a.b`, `use a.b as c` or `use a.b.{x, y}`
+    fn parse_use_stmt(&mut self) -> Result<Statement, ParseError> {
+        let mut path = vec![self.expect_peek_ident()?];
+        let mut items = None;
+        while self.peek_tok == Token::Dot {
+            self.next_token();
+            if self.peek_tok == Token::LCurly {
+                self.next_token();
+                items = Some(self.parse_use_items()?);
+                break;
+            }
+            path.push(self.expect_peek_ident()?);
+        }
+
+        // Selectively imported bindings keep their names
+        let alias = match self.peek_tok == Token::As && items.is_none() {
+            true => {
+                self.next_token();
+                Some(self.expect_peek_ident()?)
+            }
+            false => None,
+        };
+
+        Ok(Statement::Use(UseStmt { path, alias, items }))
+    }
+
+    /// First token needs to be a left curly `{`.
+    /// This function sets cur_tok to the right curly `}`
+    fn parse_use_items(&mut self) -> Result<Vec<Ident>, ParseError> {
+        let mut items = Vec::new();
+
+        self.enter(Nesting::Group);
+        while self.peek_tok != Token::RCurly {
+            items.push(self.expect_peek_ident()?);
+            self.expect_separator(&Token::RCurly)?;
+        }
+        self.leave();
+        self.next_token();
+
+        Ok(items)
+    }
+
+    /// Conditions are followed by a block, so a `{` after
+    /// an identifier does not construct a container in them
+    fn parse_cond(&mut self) -> Result<Expression, ParseError> {
+        let outer = self.cond_depth.replace(self.nesting.len());
+        let cond = self.parse_expr(Precedence::Lowest);
+        self.cond_depth = outer;
+        cond
+    }
+
+    fn instance_allowed(&self) -> bool {
+        match self.cond_depth {
+            Some(depth) => self.nesting.len() > depth,
+            None => true,
+        }
+    }
+
+    /// Parses `container` or `container { name: Type, other }`
+    fn parse_struct_expr(&mut self) -> Result<Expression, ParseError> {
+        let fields = match self.peek_tok {
+            Token::LCurly => {
+                self.next_token();
+                self.parse_ident_list(Token::RCurly)?
+            }
+            _ => Vec::new(),
+        };
+        Ok(Expression::Struct(StructExpr { fields }))
+    }
+
+    /// Current token needs to be the name of the container
+    fn parse_instance_expr(&mut self) -> Result<Expression, ParseError> {
+        let name = Ident(self.cur_tok.to_string());
+        self.next_token();
+        let mut fields = Vec::new();
+
+        self.enter(Nesting::Group);
+        while self.peek_tok != Token::RCurly {
+            let field = self.expect_peek_ident()?;
+            let val = match self.peek_tok {
+                Token::Colon => {
+                    self.next_token();
+                    self.next_token();
+                    self.parse_expr(Precedence::Lowest)?
+                }
+                _ => Expression::Ident(field.clone()),
+            };
+            fields.push(FieldInit { field, val });
+            self.expect_separator(&Token::RCurly)?;
+        }
+        self.leave();
+        self.next_token();
+
+        Ok(Expression::Instance(InstanceExpr { name, fields }))
+    }
+
+    fn parse_enum_expr(&mut self) -> Result<Expression, ParseError> {
+        self.expect_peek(Token::LCurly)?;
+        self.next_token();
+        let mut variants = Vec::new();
+
+        self.enter(Nesting::Group);
+        while self.peek_tok != Token::RCurly {
+            variants.push(self.expect_peek_ident()?);
+            self.expect_separator(&Token::RCurly)?;
+        }
+        self.leave();
+        self.next_token();
+
+        Ok(Expression::Enum(EnumExpr { variants }))
+    }
+
+    fn parse_field_expr(&mut self, val: Expression) -> Result<Expression, ParseError> {
+        let field = self.expect_peek_ident()?;
+        Ok(Expression::Field(FieldExpr {
+            val: Box::from(val),
+            field,
+        }))
+    }
+
+    fn parse_infix_expr(&mut self, left_expr: Expression) -> Result<Expression, ParseError> {
+        let op = match self.cur_tok_to_in_op() {
+            Some(op) => op,
+            None => return Err(self.cur_error(Expected::Operator)),
         };
         let prec = self.get_precedence(&self.cur_tok);
         self.next_token();
-        let right_expr = self.parse_expr(prec);
-        Expression::Infix(InfixExpr {
+        let right_expr = self.parse_expr(prec)?;
+        Ok(Expression::Infix(InfixExpr {
             left: Box::from(left_expr),
             right: Box::from(right_expr),
             op,
-        })
+        }))
+    }
+
+    /// Assignments are right associative, so
+    /// `a[0] = a[1] = 1` assigns to both indices
+    fn parse_assign_expr(&mut self, left_expr: Expression) -> Result<Expression, ParseError> {
+        let op = match self.cur_tok {
+            Token::Assign => InfixOp::Assign,
+            Token::OpAssign(Operator::Plus) => InfixOp::AddAssign,
+            Token::OpAssign(Operator::Minus) => InfixOp::SubAssign,
+            Token::OpAssign(Operator::Asterisk) => InfixOp::MulAssign,
+            Token::OpAssign(Operator::Slash) => InfixOp::DivAssign,
+            Token::OpAssign(Operator::Percent) => InfixOp::ModAssign,
+            _ => return Err(self.cur_error(Expected::Operator)),
+        };
+        if !matches!(
+            left_expr,
+            Expression::Ident(_) | Expression::Index(_) | Expression::Field(_)
+        ) {
+            return Err(ParseError {
+                kind: ParseErrorKind::InvalidAssignTarget,
+                span: self.cur_span.clone(),
+            });
+        }
+        self.next_token();
+        let right_expr = self.parse_expr(Precedence::Lowest)?;
+        Ok(Expression::Infix(InfixExpr {
+            left: Box::from(left_expr),
+            right: Box::from(right_expr),
+            op,
+        }))
+    }
+
+    fn parse_index_expr(&mut self, list: Expression) -> Result<Expression, ParseError> {
+        self.enter(Nesting::Group);
+        self.next_token();
+        let index = self.parse_expr(Precedence::Lowest)?;
+        self.expect_peek(Token::RSquare)?;
+        self.leave();
+        self.next_token();
+        Ok(Expression::Index(IndexExpr {
+            list: Box::from(list),
+            index: Box::from(index),
+        }))
     }
 
-    fn parse_prefix_expr(&mut self) -> Expression {
+    fn parse_prefix_expr(&mut self) -> Result<Expression, ParseError> {
         let op = match &self.cur_tok {
-            Token::Operator(op) => Self::reg_op_to_pre_op(op),
+            Token::Operator(Operator::Plus) => PrefixOp::Pos,
+            Token::Operator(Operator::Minus) => PrefixOp::Neg,
             Token::ExclamMark => PrefixOp::Not,
-            other => panic!("Expected operator, got: {other} instead"),
+            _ => return Err(self.cur_error(Expected::Operator)),
         };
         self.next_token();
-        let val = Box::from(self.parse_expr(Precedence::Prefix));
-        Expression::Prefix(PrefixExpr { op, val })
+        let val = Box::from(self.parse_expr(Precedence::Prefix)?);
+        Ok(Expression::Prefix(PrefixExpr { op, val }))
     }
 
-    fn cur_tok_to_in_op(&self) -> InfixOp {
-        match &self.cur_tok {
+    fn cur_tok_to_in_op(&self) -> Option<InfixOp> {
+        Some(match &self.cur_tok {
             Token::Operator(op) => Self::reg_op_to_in_op(op),
-            _ => todo!(),
-        }
-    }
-
-    fn reg_op_to_pre_op(op: &Operator) -> PrefixOp {
-        match op {
-            Operator::Plus => PrefixOp::Pos,
-            Operator::Minus => PrefixOp::Neg,
-            other => panic!("Cannot convert operator: {other} to pre op"),
-        }
+            Token::Range => InfixOp::Range,
+            Token::In => InfixOp::In,
+            Token::As => InfixOp::As,
+            Token::And => InfixOp::And,
+            Token::Or => InfixOp::Or,
+            _ => return None,
+        })
     }
 
     fn reg_op_to_in_op(op: &Operator) -> InfixOp {
@@ -354,118 +956,137 @@ impl<'a> Parser<'a> {
             Operator::Minus => InfixOp::Sub,
             Operator::Asterisk => InfixOp::Mul,
             Operator::Slash => InfixOp::Div,
+            Operator::Percent => InfixOp::Mod,
         }
     }
 
     /// First token needs to be the begin_token like `(` or `{` for example
     /// This function sets cur_tok to the end_tok
-    fn parse_ident_list(&mut self, end_tok: Token) -> Vec<OptionallyTypedIdent> {
-        if self.peek_tok == end_tok {
-            self.next_token();
-            return Vec::new();
-        }
-
-        self.next_token();
-
+    fn parse_ident_list(
+        &mut self,
+        end_tok: Token,
+    ) -> Result<Vec<OptionallyTypedIdent>, ParseError> {
         let mut items = Vec::new();
 
-        let first_item = self.parse_typed_ident();
-
-        items.push(first_item);
-
-        if self.peek_tok == Token::Comma {
-            self.next_token();
-        }
-
+        self.enter(Nesting::Group);
         while self.peek_tok != end_tok {
             self.next_token();
-            let ident = self.parse_typed_ident();
-            items.push(ident);
-            if self.peek_tok == Token::Comma {
-                self.next_token();
-            }
+            items.push(self.parse_typed_ident()?);
+            self.expect_separator(&end_tok)?;
         }
+        self.leave();
 
         self.next_token();
-        items
+        Ok(items)
     }
 
     /// First token needs to be the begin_token like `(` or `{` for example
-    fn parse_raw_list(&mut self, end_tok: Token) -> Vec<Expression> {
-        if self.peek_tok == Token::RParent {
+    fn parse_raw_list(&mut self, end_tok: Token) -> Result<Vec<Expression>, ParseError> {
+        let mut items = Vec::new();
+
+        self.enter(Nesting::Group);
+        while self.peek_tok != end_tok {
             self.next_token();
-            return Vec::new();
+            items.push(self.parse_expr(Precedence::Lowest)?);
+            self.expect_separator(&end_tok)?;
         }
+        self.leave();
 
         self.next_token();
+        Ok(items)
+    }
 
-        let mut items = Vec::new();
-
-        let first_item = self.parse_expr(Precedence::Lowest);
-
-        items.push(first_item);
-
-        if self.peek_tok == Token::Comma {
-            self.next_token();
+    /// Statements need to be followed by a newline
+    /// or the token that ends the surrounding block
+    fn expect_stmt_end(&self, end_tok: Token) -> Result<(), ParseError> {
+        match self.peek_tok {
+            Token::Eol => Ok(()),
+            ref tok if *tok == end_tok => Ok(()),
+            _ => Err(self.peek_error(Expected::OneOf(vec![Token::Eol, end_tok]))),
         }
+    }
 
-        while self.peek_tok != end_tok {
-            self.next_token();
-            let ident = self.parse_expr(Precedence::Lowest);
-            items.push(ident);
-            if self.peek_tok == Token::Comma {
+    /// Skip the comma after a list item. If there is none,
+    /// the list has to end after the item
+    fn expect_separator(&mut self, end_tok: &Token) -> Result<(), ParseError> {
+        match self.peek_tok {
+            Token::Comma => {
                 self.next_token();
+                Ok(())
             }
+            ref tok if tok == end_tok => Ok(()),
+            _ => Err(self.peek_error(Expected::OneOf(vec![Token::Comma, end_tok.clone()]))),
         }
-
-        self.next_token();
-        items
     }
 
-    /// First token needs to be a left curly `{`
-    fn parse_block_stmt(&mut self) -> BlockStmt {
+    /// First token needs to be a left curly `{`.
+    /// This function sets cur_tok to the right curly `}`.
+    /// Parsing continues after an error at the next statement
+    /// of the block. The last error of the block is returned
+    /// after the whole block was parsed
+    fn parse_block_stmt(&mut self) -> Result<BlockStmt, ParseError> {
         let mut stmts = Vec::new();
-
-        self.next_token();
-        while self.peek_tok != Token::RCurly {
-            self.next_token();
-            let stmt = self
-                .parse_stmt()
-                .expect("Found eof even though the blockstatement was not yet fully parsed");
-            stmts.push(stmt);
-            self.next_token();
+        let errors = self.errors.len();
+        let (labels, cond_depth) = (self.labels.len(), self.cond_depth);
+
+        self.enter(Nesting::Block);
+        let depth = self.nesting.len();
+        loop {
+            match self.peek_tok {
+                Token::RCurly => break,
+                Token::Eof => return Err(self.peek_error(Expected::Token(Token::RCurly))),
+                Token::Eol => self.next_token(),
+                _ => {
+                    self.next_token();
+                    match self
+                        .parse_stmt()
+                        .and_then(|stmt| self.expect_stmt_end(Token::RCurly).map(|_| stmt))
+                    {
+                        Ok(stmt) => stmts.push(stmt),
+                        Err(err) => {
+                            self.labels.truncate(labels);
+                            self.cond_depth = cond_depth;
+                            if self.synchronize(&err, depth) {
+                                self.leave();
+                                return Err(err);
+                            }
+                            self.errors.push(err);
+                        }
+                    }
+                }
+            }
         }
+        self.leave();
         self.next_token();
-        BlockStmt { stmts }
+        match self.errors.len() > errors {
+            true => Err(self.errors.pop().unwrap()),
+            false => Ok(BlockStmt { stmts }),
+        }
     }
 
-    fn parse_typed_ident(&mut self) -> OptionallyTypedIdent {
-        let ident = Ident(self.cur_tok.to_string());
+    fn parse_typed_ident(&mut self) -> Result<OptionallyTypedIdent, ParseError> {
+        let ident = match self.cur_tok {
+            Token::Ident(ref ident) => Ident(ident.clone()),
+            _ => return Err(self.cur_error(Expected::Ident)),
+        };
         let _type = match self.peek_tok {
             Token::Colon => {
                 self.next_token();
-                self.next_token();
-                Some(Ident(self.cur_tok.to_string()))
+                Some(self.expect_peek_ident()?)
             }
             _ => None,
         };
-        OptionallyTypedIdent { ident, _type }
+        Ok(OptionallyTypedIdent { ident, _type })
     }
 
-    fn parse_variable(&mut self, is_const: bool) -> Statement {
-        let name = Ident(match self.peek_tok {
-            Token::Ident(_) => self.peek_tok.to_string(),
-            _ => panic!("Expected an identifier, received: {}", self.peek_tok),
-        });
-
-        self.next_token();
+    fn parse_variable(&mut self, is_const: bool) -> Result<Statement, ParseError> {
+        let name = self.expect_peek_ident()?;
 
         let _type = match self.peek_tok {
             Token::Colon => {
                 self.next_token();
-                let ident = Ident(self.peek_tok.to_string());
-                self.next_token();
-                self.expect_peek(Token::Assign);
+                let ident = self.expect_peek_ident()?;
+                self.expect_peek(Token::Assign)?;
                 self.next_token();
                 Some(ident)
             }
@@ -473,41 +1094,41 @@ impl<'a> Parser<'a> {
                 self.next_token();
                 None
             }
-            _ => panic!("Expected Assign, received: {}", self.peek_tok),
+            _ => return Err(self.peek_error(Expected::OneOf(vec![Token::Colon, Token::Assign]))),
         };
 
         self.next_token();
 
-        let val = self.parse_expr(Precedence::Lowest);
+        let val = self.parse_expr(Precedence::Lowest)?;
 
-        Statement::Variable(VarStmt {
+        Ok(Statement::Variable(VarStmt {
             name: OptionallyTypedIdent { ident: name, _type },
             val,
             is_const,
-        })
+        }))
     }
 
-    fn parse_quick_assign(&mut self) -> Statement {
-        let name = Ident(match self.cur_tok {
-            Token::Ident(_) => self.cur_tok.to_string(),
-            // unreachable
-            _ => panic!("Expected an identifier, received: {}", self.cur_tok),
-        });
+    fn parse_quick_assign(&mut self) -> Result<Statement, ParseError> {
+        let name = match self.cur_tok {
+            Token::Ident(ref ident) => Ident(ident.clone()),
+            _ => return Err(self.cur_error(Expected::Ident)),
+        };
 
         let is_const;
 
         let _type = match self.peek_tok {
             Token::Colon => {
                 self.next_token();
-                let ident = Ident(self.peek_tok.to_string());
-                self.next_token();
+                let ident = self.expect_peek_ident()?;
                 match self.peek_tok {
                     Token::ConstAssign => is_const = true,
                     Token::VarAssign => is_const = false,
-                    _ => panic!(
-                        "Expected ConstAssign or VarAssign, received: {}",
-                        self.peek_tok
-                    ),
+                    _ => {
+                        return Err(self.peek_error(Expected::OneOf(vec![
+                            Token::ConstAssign,
+                            Token::VarAssign,
+                        ])))
+                    }
                 }
                 Some(ident)
             }
@@ -519,32 +1140,151 @@ impl<'a> Parser<'a> {
                 is_const = false;
                 None
             }
-            _ => panic!("Expected Assign, received: {}", self.peek_tok),
+            _ => {
+                return Err(self.peek_error(Expected::OneOf(vec![
+                    Token::Colon,
+                    Token::ConstAssign,
+                    Token::VarAssign,
+                ])))
+            }
         };
 
         self.next_token();
         self.next_token();
 
-        let val = self.parse_expr(Precedence::Lowest);
+        let val = self.parse_expr(Precedence::Lowest)?;
 
-        Statement::Variable(VarStmt {
+        Ok(Statement::Variable(VarStmt {
             name: OptionallyTypedIdent { ident: name, _type },
             val,
             is_const,
-        })
+        }))
     }
 
-    fn parse_call_expr(&mut self, func: Expression) -> Expression {
-        let args = self.parse_raw_list(Token::RParent);
-        Expression::Call(CallExpr {
+    fn parse_call_expr(&mut self, func: Expression) -> Result<Expression, ParseError> {
+        let args = self.parse_raw_list(Token::RParent)?;
+        Ok(Expression::Call(CallExpr {
             ident: Box::from(func),
             args,
-        })
+        }))
+    }
+
+    fn expect_peek(&self, expected: Token) -> Result<(), ParseError> {
+        match self.peek_tok == expected {
+            true => Ok(()),
+            false => Err(self.peek_error(Expected::Token(expected))),
+        }
+    }
+
+    /// Expect the peek token to be an identifier and
+    /// advance to it
+    fn expect_peek_ident(&mut self) -> Result<Ident, ParseError> {
+        match self.peek_tok {
+            Token::Ident(ref ident) => {
+                let ident = Ident(ident.clone());
+                self.next_token();
+                Ok(ident)
+            }
+            _ => Err(self.peek_error(Expected::Ident)),
+        }
+    }
+
+    fn cur_error(&self, expected: Expected) -> ParseError {
+        self.error(expected, &self.cur_tok, &self.cur_span)
+    }
+
+    fn peek_error(&self, expected: Expected) -> ParseError {
+        self.error(expected, &self.peek_tok, &self.peek_span)
+    }
+
+    /// Illegal tokens are reported as the lex error
+    /// they were created from
+    fn error(&self, expected: Expected, found: &Token, span: &Span) -> ParseError {
+        let lex_error = match found {
+            Token::Illegal(_) => self.lex_errors.iter().rev().find(|err| &err.span == span),
+            _ => None,
+        };
+        ParseError {
+            kind: match lex_error {
+                Some(err) => ParseErrorKind::Lex(err.kind.clone()),
+                None => ParseErrorKind::Unexpected {
+                    expected,
+                    found: found.clone(),
+                },
+            },
+            span: span.clone(),
+        }
+    }
+
+    /// Skip to the end of the statement an error occurred in, so the
+    /// peek token is the one after it. `depth` is the nesting of the
+    /// surrounding block or zero at the top level. Blocks that are still
+    /// open are skipped as well, while other brackets end at the next line.
+    /// Returns true if the current token is the `}` that ends the block
+    fn synchronize(&mut self, err: &ParseError, depth: usize) -> bool {
+        let mut open = self.nesting.split_off(depth);
+        let in_block = depth > 0;
+        // Brackets before the error token were already entered
+        // or left, while the error token itself was not
+        if err.span == self.cur_span && Self::skip_bracket(&mut open, &self.cur_tok, in_block) {
+            return true;
+        }
+        loop {
+            if self.peek_tok == Token::Eof {
+                return false;
+            }
+            if self.peek_tok == Token::Eol || self.peek_after_eol || self.cur_tok == Token::Eol {
+                while open.last() == Some(&Nesting::Group) {
+                    open.pop();
+                }
+                if open.is_empty() {
+                    return false;
+                }
+            }
+            if Self::skip_bracket(&mut open, &self.peek_tok, in_block) {
+                return false;
+            }
+            self.next_token();
+        }
+    }
+
+    /// Tracks the brackets that are opened and closed while skipping.
+    /// Returns true if `tok` is the `}` that ends the surrounding block
+    fn skip_bracket(open: &mut Vec<Nesting>, tok: &Token, in_block: bool) -> bool {
+        match tok {
+            Token::LCurly => open.push(Nesting::Block),
+            Token::LParent | Token::LSquare => open.push(Nesting::Group),
+            Token::RCurly if open.is_empty() => return in_block,
+            Token::RCurly => {
+                open.pop();
+            }
+            // Blocks can not be closed by other brackets
+            Token::RParent | Token::RSquare if open.last() == Some(&Nesting::Group) => {
+                open.pop();
+            }
+            _ => (),
+        }
+        false
+    }
+
+    /// Called with the opening bracket as the current token
+    fn enter(&mut self, nesting: Nesting) {
+        self.nesting.push(nesting);
+        self.skip_peek_eol();
     }
 
-    fn expect_peek(&self, expected: Token) {
-        if self.peek_tok != expected {
-            panic!("Expected: {}, received: {}", expected, self.peek_tok)
+    /// Needs to be called before the closing bracket becomes the
+    /// current token, so the token after it is read with the
+    /// newline handling of the surrounding brackets
+    fn leave(&mut self) {
+        self.nesting.pop();
+        self.skip_peek_eol();
+    }
+
+    fn skip_peek_eol(&mut self) {
+        while self.peek_tok == Token::Eol && self.nesting.last() == Some(&Nesting::Group) {
+            self.read_peek();
+            self.peek_after_eol = true;
         }
     }
 
@@ -552,35 +1292,166 @@ impl<'a> Parser<'a> {
         matches!(self.peek_tok, Token::Eol | Token::Eof)
     }
 
+    /// Checks if the statement can end before the peek token,
+    /// for statements with optional values like `return`
+    fn peek_is_stmt_end(&self) -> bool {
+        matches!(
+            self.peek_tok,
+            Token::Eol | Token::Eof | Token::RCurly | Token::Comma
+        )
+    }
+
+    /// The token after the peek token
+    fn peek_second(&mut self) -> &Token {
+        if self.queued.is_empty() {
+            let tok = match self.peek_tok {
+                Token::Eof => Self::eof(&self.peek_span),
+                _ => Self::lex_next(self.lexer, &mut self.lex_errors),
+            };
+            self.queued.push_back(tok);
+        }
+        &self.queued[0].tok
+    }
+
     pub fn next_token(&mut self) {
         swap(&mut self.cur_tok, &mut self.peek_tok);
-        self.peek_tok = util::get_next_tok(self.lexer);
+        swap(&mut self.cur_span, &mut self.peek_span);
+        self.read_peek();
+        self.skip_peek_eol();
+    }
+
+    fn read_peek(&mut self) {
+        let peek = match self.queued.pop_front() {
+            Some(tok) => tok,
+            // Nothing follows `Eof`, which also ends embedded expressions
+            None if self.cur_tok == Token::Eof => Self::eof(&self.cur_span),
+            None => Self::lex_next(self.lexer, &mut self.lex_errors),
+        };
+        self.peek_tok = peek.tok;
+        self.peek_span = peek.span;
+        self.peek_after_eol = false;
+    }
+
+    fn eof(span: &Span) -> SpannedToken {
+        SpannedToken {
+            tok: Token::Eof,
+            span: span.clone(),
+            trivia: Vec::new(),
+        }
+    }
+
+    fn lex_next(lexer: &mut Lexer, errors: &mut Vec<LexError>) -> SpannedToken {
+        match lexer.tokenize() {
+            Ok(tok) => tok,
+            Err(err) => {
+                let tok = err.to_token();
+                errors.push(err);
+                tok
+            }
+        }
     }
 
     fn get_precedence(&self, token: &Token) -> Precedence {
         match token {
-            Token::Assign => Precedence::Assign,
+            Token::Assign | Token::OpAssign(_) => Precedence::Assign,
             Token::Operator(op) => match op {
                 Operator::Equals | Operator::NotEquals => Precedence::Equals,
                 Operator::Greater | Operator::Lesser => Precedence::LessGreater,
                 Operator::GreaterEquals | Operator::LesserEquals => Precedence::LessGreaterOrEqual,
                 Operator::Plus | Operator::Minus => Precedence::Sum,
-                Operator::Asterisk | Operator::Slash => Precedence::Product,
+                Operator::Asterisk | Operator::Slash | Operator::Percent => Precedence::Product,
             },
+            Token::Or => Precedence::Or,
+            Token::And => Precedence::And,
+            Token::In => Precedence::Contains,
+            Token::Range => Precedence::Range,
+            Token::As => Precedence::Conversion,
             Token::LParent => Precedence::Call,
-            Token::LSquare => Precedence::Index,
+            Token::LSquare | Token::Dot => Precedence::Index,
             _ => Precedence::Lowest,
         }
     }
 }
 
-#[derive(Debug)]
-pub struct EofError;
+#[derive(Debug, PartialEq, Clone)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ParseErrorKind {
+    /// A token was found where a different one was expected
+    Unexpected { expected: Expected, found: Token },
+    /// The lexer could not tokenize the input
+    Lex(LexErrorKind),
+    /// Multiple `local` keywords in a row
+    StackedLocal,
+    /// A list pattern with more than one rest pattern
+    MultipleRest,
+    /// `continue` with a label that no surrounding loop has
+    UnknownLabel(String),
+    /// Assignment to something other than an identifier, index or field
+    InvalidAssignTarget,
+    /// There are no statements left to parse
+    Eof,
+}
+
+/// What the parser was looking for when it encountered an error
+#[derive(Debug, PartialEq, Clone)]
+pub enum Expected {
+    Token(Token),
+    OneOf(Vec<Token>),
+    Ident,
+    Expression,
+    Operator,
+    Statement,
+    Pattern,
+}
+
+impl Error for ParseError {}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at {}", self.kind, self.span)
+    }
+}
 
-impl Error for EofError {}
+impl Display for ParseErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseErrorKind::Unexpected { expected, found } => {
+                write!(f, "Expected {}, found `{}`", expected, found)
+            }
+            ParseErrorKind::Lex(kind) => write!(f, "{}", kind),
+            ParseErrorKind::StackedLocal => f.write_str("Cannot stack multiple `local` statements"),
+            ParseErrorKind::InvalidAssignTarget => {
+                f.write_str("Only identifiers, indices and fields can be assigned to")
+            }
+            ParseErrorKind::UnknownLabel(label) => {
+                write!(f, "There is no surrounding loop with the label {}", label)
+            }
+            ParseErrorKind::MultipleRest => {
+                f.write_str("A list pattern cannot contain more than one rest pattern `...`")
+            }
+            ParseErrorKind::Eof => f.write_str("Encountered end of file"),
+        }
+    }
+}
 
-impl Display for EofError {
+impl Display for Expected {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str("Encountered end of file")
+        match self {
+            Expected::Token(tok) => write!(f, "`{}`", tok),
+            Expected::OneOf(toks) => {
+                let toks: Vec<_> = toks.iter().map(|tok| format!("`{}`", tok)).collect();
+                write!(f, "one of {}", toks.join(", "))
+            }
+            Expected::Ident => f.write_str("an identifier"),
+            Expected::Expression => f.write_str("an expression"),
+            Expected::Operator => f.write_str("an operator"),
+            Expected::Statement => f.write_str("a statement"),
+            Expected::Pattern => f.write_str("a pattern"),
+        }
     }
 }
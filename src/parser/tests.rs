@@ -1,6 +1,15 @@
 #[cfg(test)]
 mod tests {
-    use crate::{lexer::Lexer, parser::Parser};
+    use crate::{
+        lexer::{tokens::Token, LexErrorKind, Lexer, ANONYMOUS_SOURCE},
+        parser::{
+            ast::{
+                Expression, Ident, InfixExpr, InfixOp, Literal, LoopType, Pattern, PrefixExpr,
+                PrefixOp, Statement,
+            },
+            Expected, ParseErrorKind, Parser,
+        },
+    };
 
     #[test]
     fn test_parser() {
@@ -9,4 +18,563 @@ mod tests {
         let stmt = parser.parse_stmt().expect("Failed to parse");
         println!("{:#?}", stmt);
     }
+
+    /// Test for checking if a whole program is parsed
+    #[test]
+    fn test_program() {
+        let mut lexer = Lexer::from_string(
+            "x := 1\n\nadd :: func(a: int, b: int): int {\n    return a + b\n}\nadd(x, 2)\n",
+            None,
+        );
+        let mut parser = Parser::new(&mut lexer);
+        let program = parser.parse_program().expect("Failed to parse");
+        assert_eq!(ANONYMOUS_SOURCE, program.source_id);
+        let stmts = program.stmts;
+        assert_eq!(3, stmts.len());
+        assert!(matches!(stmts[0], Statement::Variable(_)));
+        assert!(matches!(stmts[1], Statement::Variable(_)));
+        assert!(matches!(
+            stmts[2],
+            Statement::Expression(Expression::Call(_))
+        ));
+    }
+
+    /// Test for checking if every expression of
+    /// a program can be displayed
+    #[test]
+    fn test_program_display() {
+        let mut lexer = Lexer::from_string(
+            "f :: func(a: Int, b): Int { print(a, b) }\nif a { 1 } else if b { 2 } else { f(1, 2) }",
+            None,
+        );
+        let mut parser = Parser::new(&mut lexer);
+        let program = parser.parse_program().expect("Failed to parse");
+        assert_eq!(
+            "const f = func(a: Int, b): Int {\n    print(a, b)\n}\n\
+             if a {\n    1\n} else if b {\n    2\n} else {\n    f(1, 2)\n}\n",
+            program.to_string()
+        );
+    }
+
+    /// Test for checking if list literals and index
+    /// expressions are parsed with the right precedence
+    #[test]
+    fn test_lists() {
+        let mut lexer =
+            Lexer::from_string("[1, x + 2, [3]]\nxs[i + 1][0] * 2\nxs[0] = ys[1] = 4", None);
+        let mut parser = Parser::new(&mut lexer);
+        let program = parser.parse_program().expect("Failed to parse");
+        let stmts: Vec<_> = program.stmts.iter().map(|stmt| stmt.to_string()).collect();
+        assert_eq!(
+            vec!["[1, x + 2, [3]]", "xs[i + 1][0] * 2", "xs[0] = ys[1] = 4"],
+            stmts
+        );
+    }
+
+    /// Test for checking if newlines are ignored inside of
+    /// brackets while still separating statements after them
+    #[test]
+    fn test_multi_line_exprs() {
+        let mut lexer = Lexer::from_string(
+            "(a + b) * -(c)\nxs := [\n    1,\n    (2\n        + 3),\n]\nxs[\n    0\n]\n(xs)\nadd :: func(\n    a: int,\n    b: int,\n) {\n    return a\n}\nadd(\n    1,\n    2,\n)\n",
+            None,
+        );
+        let mut parser = Parser::new(&mut lexer);
+        let program = parser.parse_program().expect("Failed to parse");
+        let stmts = program.stmts;
+        assert_eq!(6, stmts.len());
+        assert_eq!("(a + b) * -c", stmts[0].to_string());
+        assert_eq!("var xs = [1, 2 + 3]", stmts[1].to_string());
+        assert_eq!("xs[0]", stmts[2].to_string());
+        assert_eq!("xs", stmts[3].to_string());
+        match &stmts[5] {
+            Statement::Expression(Expression::Call(call)) => assert_eq!(2, call.args.len()),
+            other => panic!("Expected a call, got: {:?}", other),
+        }
+        match &stmts[0] {
+            Statement::Expression(Expression::Infix(infix)) => {
+                assert_eq!("a + b", infix.left.to_string())
+            }
+            other => panic!("Expected an infix expression, got: {:?}", other),
+        }
+    }
+
+    /// Test for checking if both forms of when
+    /// expressions are parsed into their cases
+    #[test]
+    fn test_when() {
+        let mut lexer = Lexer::from_string(
+            "when x + 1 {\n    1 -> a, 2 -> {\n        b\n    }\n    else -> c\n}\nwhen { x > 1 -> a }",
+            None,
+        );
+        let mut parser = Parser::new(&mut lexer);
+        let program = parser.parse_program().expect("Failed to parse");
+        let stmts: Vec<_> = program.stmts.iter().map(|stmt| stmt.to_string()).collect();
+        assert_eq!(
+            vec![
+                "when x + 1 { 1 -> a, 2 -> {\n    b\n}, else -> c }",
+                "when { x > 1 -> a }"
+            ],
+            stmts
+        );
+    }
+
+    /// Test for checking if the arms of when expressions
+    /// with a compared value are parsed as patterns
+    #[test]
+    fn test_patterns() {
+        let mut lexer = Lexer::from_string(
+            "when x {\n    -1 -> a\n    1..10 -> b\n    none -> c\n    [first, ...rest, last] -> d\n    [_, ...] -> e\n    Point { x, y: 0 } -> f\n    Color.Red -> g\n    (y + 1) -> h\n    other -> i\n}",
+            None,
+        );
+        let mut parser = Parser::new(&mut lexer);
+        let program = parser.parse_program().expect("Failed to parse");
+        let cases = match &program.stmts[0] {
+            Statement::Expression(Expression::When(when)) => &when.cases,
+            other => panic!("Expected a when expression, got: {:?}", other),
+        };
+        let patterns: Vec<_> = cases
+            .iter()
+            .map(|case| case.pattern.as_ref().expect("Missing pattern"))
+            .collect();
+        assert_eq!(
+            Pattern::Value(Expression::Prefix(PrefixExpr {
+                op: PrefixOp::Neg,
+                val: Box::new(Expression::Literal(Literal::Int(1))),
+            })),
+            *patterns[0]
+        );
+        assert!(matches!(patterns[1], Pattern::Range(_)));
+        assert_eq!(Pattern::None, *patterns[2]);
+        assert_eq!(Pattern::Binding(Ident("other".into())), *patterns[8]);
+        let patterns: Vec<_> = patterns.iter().map(|pattern| pattern.to_string()).collect();
+        assert_eq!(
+            vec![
+                "-1",
+                "1..10",
+                "none",
+                "[first, ...rest, last]",
+                "[_, ...]",
+                "Point { x, y: 0 }",
+                "Color.Red",
+                "y + 1",
+                "other"
+            ],
+            patterns
+        );
+
+        let mut lexer = Lexer::from_string("when x { [...a, ...b] -> 1 }", None);
+        let mut parser = Parser::new(&mut lexer);
+        let errors = parser.parse_program().expect_err("Parsed invalid pattern");
+        assert_eq!(ParseErrorKind::MultipleRest, errors[0].kind);
+    }
+
+    /// Test for checking if all loop forms and their
+    /// else branches are parsed into the right loop type
+    #[test]
+    fn test_loops() {
+        let mut lexer = Lexer::from_string(
+            "loop {\n    x\n}\nloop x < 10 { x }\nloop i in 0..10 { i } else loop x in xs { x } else { y }\nloop x in xs == false { x }",
+            None,
+        );
+        let mut parser = Parser::new(&mut lexer);
+        let program = parser.parse_program().expect("Failed to parse");
+        let loops: Vec<_> = program
+            .stmts
+            .iter()
+            .map(|stmt| match stmt {
+                Statement::Expression(Expression::Loop(_loop)) => _loop,
+                other => panic!("Expected a loop, got: {:?}", other),
+            })
+            .collect();
+        assert_eq!(LoopType::While, loops[0]._type);
+        assert_eq!(None, loops[0].cond);
+        assert_eq!(LoopType::While, loops[1]._type);
+        assert_eq!(LoopType::For, loops[2]._type);
+        let alt = loops[2].alt.as_ref().expect("Missing else branch");
+        assert_eq!(LoopType::ElseFor, alt._type);
+        assert_eq!(
+            LoopType::Else,
+            alt.alt.as_ref().expect("Missing else")._type
+        );
+        // `in` has a lower precedence than `==`, so this is a for loop
+        assert_eq!(LoopType::For, loops[3]._type);
+        assert_eq!(
+            "loop i in 0..10 {\n    i\n} else loop x in xs {\n    x\n} else {\n    y\n}",
+            loops[2].to_string()
+        );
+    }
+
+    /// Test for checking if labels of surrounding loops are told
+    /// apart from the values of break statements
+    #[test]
+    fn test_labels() {
+        let mut lexer = Lexer::from_string(
+            "outer: loop i in xs {\n    loop {\n        break outer i\n        break i\n        continue outer\n    }\n}\nx: int := 1",
+            None,
+        );
+        let mut parser = Parser::new(&mut lexer);
+        let program = parser.parse_program().expect("Failed to parse");
+        assert_eq!(
+            "outer: loop i in xs {\n    loop {\n    break outer i\n    break i\n    continue outer\n}\n}",
+            program.stmts[0].to_string()
+        );
+        assert_eq!("var x: int = 1", program.stmts[1].to_string());
+
+        let mut lexer = Lexer::from_string("loop {\n    continue outer\n}", None);
+        let mut parser = Parser::new(&mut lexer);
+        let errors = parser.parse_program().expect_err("Parsed unknown label");
+        assert_eq!(ParseErrorKind::UnknownLabel("outer".into()), errors[0].kind);
+    }
+
+    /// Test for checking if module paths, aliases and
+    /// selectively imported bindings are parsed
+    #[test]
+    fn test_use() {
+        let mut lexer = Lexer::from_string(
+            "use std\nuse a.b.c\nuse a.b as c\nuse a.b.{\n    x,\n    y,\n}",
+            None,
+        );
+        let mut parser = Parser::new(&mut lexer);
+        let program = parser.parse_program().expect("Failed to parse");
+        let stmts: Vec<_> = program.stmts.iter().map(|stmt| stmt.to_string()).collect();
+        assert_eq!(
+            vec!["use std", "use a.b.c", "use a.b as c", "use a.b.{x, y}"],
+            stmts
+        );
+
+        let mut lexer = Lexer::from_string("use a.", None);
+        let mut parser = Parser::new(&mut lexer);
+        let errors = parser.parse_program().expect_err("Parsed incomplete path");
+        assert_eq!(
+            ParseErrorKind::Unexpected {
+                expected: Expected::Ident,
+                found: Token::Eof
+            },
+            errors[0].kind
+        );
+    }
+
+    /// Test for checking if containers are declared and constructed,
+    /// but not at the top level of conditions that are followed by a block
+    #[test]
+    fn test_containers() {
+        let mut lexer = Lexer::from_string(
+            "Point :: container {\n    x: Int,\n    y,\n}
+            Empty :: container
+            p :: Point { x: 1, y }
+            if p == (Point { x: 1, y: 2 }) { p.x = 2 }
+            loop q in points { q.y += 1 }",
+            None,
+        );
+        let mut parser = Parser::new(&mut lexer);
+        let program = parser.parse_program().expect("Failed to parse");
+        let stmts: Vec<_> = program.stmts.iter().map(|stmt| stmt.to_string()).collect();
+        assert_eq!(
+            vec![
+                "const Point = container { x: Int, y }",
+                "const Empty = container",
+                "const p = Point { x: 1, y }",
+                "if p == Point { x: 1, y: 2 } {\n    p.x = 2\n}",
+                "loop q in points {\n    q.y += 1\n}",
+            ],
+            stmts
+        );
+    }
+
+    /// Test for checking if `or` binds weaker than `and`
+    /// and both bind weaker than comparisons
+    #[test]
+    fn test_logical_ops() {
+        let mut lexer = Lexer::from_string("a or !b and c == 1", None);
+        let mut parser = Parser::new(&mut lexer);
+        let program = parser.parse_program().expect("Failed to parse");
+        let Statement::Expression(Expression::Infix(InfixExpr { op, right, .. })) =
+            &program.stmts[0]
+        else {
+            panic!("Expected an infix expression, got: {}", program.stmts[0]);
+        };
+        assert_eq!(InfixOp::Or, *op);
+        let Expression::Infix(InfixExpr { op, left, right }) = right.as_ref() else {
+            panic!("Expected an infix expression, got: {right}");
+        };
+        assert_eq!(InfixOp::And, *op);
+        assert!(matches!(
+            left.as_ref(),
+            Expression::Prefix(PrefixExpr {
+                op: PrefixOp::Not,
+                ..
+            })
+        ));
+        assert!(matches!(
+            right.as_ref(),
+            Expression::Infix(InfixExpr {
+                op: InfixOp::Eq,
+                ..
+            })
+        ));
+    }
+
+    /// Test for checking if assignments are right associative
+    /// and only accept identifiers, indices and fields as targets
+    #[test]
+    fn test_assignments() {
+        let mut lexer = Lexer::from_string("x = y = 1\nxs[0] += 2 * 3\np.x -= 1\nx %= 2", None);
+        let mut parser = Parser::new(&mut lexer);
+        let program = parser.parse_program().expect("Failed to parse");
+        let stmts: Vec<_> = program.stmts.iter().map(|stmt| stmt.to_string()).collect();
+        assert_eq!(
+            vec!["x = y = 1", "xs[0] += 2 * 3", "p.x -= 1", "x %= 2"],
+            stmts
+        );
+
+        let mut lexer = Lexer::from_string("1 + x = 2", None);
+        let mut parser = Parser::new(&mut lexer);
+        let errors = parser
+            .parse_program()
+            .expect_err("Parsed invalid assignment");
+        assert_eq!(ParseErrorKind::InvalidAssignTarget, errors[0].kind);
+    }
+
+    /// Test for checking if every syntax error of a file
+    /// is reported with the expected and found token
+    #[test]
+    fn test_errors() {
+        let mut lexer = Lexer::from_string(
+            "var = 1\nok := 2\nf :: func() {\n    x := 1 +\n    y := 2\n}\nz := $\nconst c 3\n",
+            Some("errors.nx"),
+        );
+        let mut parser = Parser::new(&mut lexer);
+        let errors = parser.parse_program().expect_err("Parsed invalid program");
+        let errors: Vec<_> = errors
+            .into_iter()
+            .map(|err| (err.kind, err.span.line, err.span.col))
+            .collect();
+        assert_eq!(
+            vec![
+                (
+                    ParseErrorKind::Unexpected {
+                        expected: Expected::Ident,
+                        found: Token::Assign,
+                    },
+                    1,
+                    5
+                ),
+                (
+                    ParseErrorKind::Unexpected {
+                        expected: Expected::Expression,
+                        found: Token::Eol,
+                    },
+                    4,
+                    13
+                ),
+                (ParseErrorKind::Lex(LexErrorKind::InvalidSymbol('$')), 7, 6),
+                (
+                    ParseErrorKind::Unexpected {
+                        expected: Expected::OneOf(vec![Token::Colon, Token::Assign]),
+                        found: Token::Literal(crate::lexer::tokens::Literal::Int(3)),
+                    },
+                    8,
+                    9
+                ),
+            ],
+            errors
+        );
+    }
+
+    /// Test for checking if parsing continues at the next
+    /// statement of the surrounding block after an error and
+    /// if unclosed brackets end at the end of their line
+    #[test]
+    fn test_recovery() {
+        let mut lexer = Lexer::from_string(
+            "f :: func() {\n  x := )\n  y := 1\n}\n\
+             g :: func() {\n  a := )\n  if a {\n    b := ]\n  }\n  c := [1\n  d := 2 +\n}\n\
+             x := (1\ny := 2\nz := }\nok :: 1\n\
+             w := when ok {\n  1 -> )\n  2 -> 3\n}\nlast := ]",
+            None,
+        );
+        let mut parser = Parser::new(&mut lexer);
+        let errors = parser.parse_program().expect_err("Parsed invalid program");
+        let errors: Vec<_> = errors
+            .into_iter()
+            .map(|err| (err.kind, err.span.line, err.span.col))
+            .collect();
+        let unexpected =
+            |expected: Expected, found: Token| ParseErrorKind::Unexpected { expected, found };
+        assert_eq!(
+            vec![
+                (unexpected(Expected::Expression, Token::RParent), 2, 8),
+                (unexpected(Expected::Expression, Token::RParent), 6, 8),
+                (unexpected(Expected::Expression, Token::RSquare), 8, 10),
+                (
+                    unexpected(
+                        Expected::OneOf(vec![Token::Comma, Token::RSquare]),
+                        Token::Ident(String::from("d"))
+                    ),
+                    11,
+                    3
+                ),
+                (unexpected(Expected::Expression, Token::Eol), 11, 11),
+                (
+                    unexpected(
+                        Expected::Token(Token::RParent),
+                        Token::Ident(String::from("y"))
+                    ),
+                    14,
+                    1
+                ),
+                (unexpected(Expected::Expression, Token::RCurly), 15, 6),
+                (unexpected(Expected::Expression, Token::RParent), 18, 8),
+                (unexpected(Expected::Expression, Token::RSquare), 21, 9),
+            ],
+            errors
+        );
+    }
+
+    /// Test for checking if statements on the same
+    /// line are rejected instead of parsed one by one
+    #[test]
+    fn test_stmt_separators() {
+        let mut lexer = Lexer::from_string(
+            "x :: 1 2 3\nloop { break } x\nf :: func() { a b }\nok :: 1",
+            None,
+        );
+        let mut parser = Parser::new(&mut lexer);
+        let errors = parser.parse_program().expect_err("Parsed invalid program");
+        let errors: Vec<_> = errors
+            .into_iter()
+            .map(|err| (err.kind, err.span.line, err.span.col))
+            .collect();
+        let unexpected = |end_tok: Token, found: &str| ParseErrorKind::Unexpected {
+            expected: Expected::OneOf(vec![Token::Eol, end_tok]),
+            found: match found.parse() {
+                Ok(int) => Token::Literal(crate::lexer::tokens::Literal::Int(int)),
+                Err(_) => Token::Ident(found.into()),
+            },
+        };
+        assert_eq!(
+            vec![
+                (unexpected(Token::Eof, "2"), 1, 8),
+                (unexpected(Token::Eof, "x"), 2, 16),
+                (unexpected(Token::RCurly, "b"), 3, 17),
+            ],
+            errors
+        );
+    }
+
+    /// Test for checking if statements can be followed by
+    /// comments at the top level and inside of blocks
+    #[test]
+    fn test_trailing_comments() {
+        let mut lexer = Lexer::from_string(
+            "# header\nx := 1 # note\nx + 1 # encased # # note\n\
+             f :: func() {\n    # own line\n    y := 1 # note\n    y + 1 # note\n} # note\n",
+            None,
+        );
+        let mut parser = Parser::new(&mut lexer);
+        let program = parser.parse_program().expect("Failed to parse");
+        let stmts: Vec<_> = program.stmts.iter().map(|stmt| stmt.to_string()).collect();
+        assert_eq!(
+            vec![
+                "var x = 1",
+                "x + 1",
+                "const f = func() {\n    var y = 1\n    y + 1\n}",
+            ],
+            stmts
+        );
+    }
+
+    /// Test for checking if displayed expressions keep the
+    /// parentheses that are needed to parse them the same way
+    #[test]
+    fn test_display_round_trip() {
+        let source = "(1 + 2) * 3\n1 - (2 - 3)\n1 - 2 - 3\n-(1 + 2) * -x\n(-x) as Int\n\
+                      (a or b) and !(c == d)\nx = y = 1 + 2\nx += (1 + 2) % 3\n\
+                      xs[(i + 1) * 2]\n0..n - 1\nf((a + b) * c)";
+        let mut lexer = Lexer::from_string(source, None);
+        let mut parser = Parser::new(&mut lexer);
+        let program = parser.parse_program().expect("Failed to parse");
+        let displayed = program.to_string();
+        assert_eq!(
+            "(1 + 2) * 3\n1 - (2 - 3)\n1 - 2 - 3\n-(1 + 2) * -x\n(-x) as Int\n\
+             (a or b) and !(c == d)\nx = y = 1 + 2\nx += (1 + 2) % 3\n\
+             xs[(i + 1) * 2]\n0..n - 1\nf((a + b) * c)\n",
+            displayed
+        );
+
+        let mut lexer = Lexer::from_string(displayed, None);
+        let mut parser = Parser::new(&mut lexer);
+        assert_eq!(program, parser.parse_program().expect("Failed to reparse"));
+    }
+
+    /// Test for checking if errors in embedded expressions of
+    /// strings point into the string without skipping the
+    /// tokens after it
+    #[test]
+    fn test_interpolation_errors() {
+        let mut lexer = Lexer::from_string(
+            "s :: \"{a +}\"\nok :: \"{ok} {a b}\"\nlast :: \"\"\"\n{(1 +\n  2}\"\"\"\nend :: 1",
+            None,
+        );
+        let mut parser = Parser::new(&mut lexer);
+        let errors = parser.parse_program().expect_err("Parsed invalid program");
+        let errors: Vec<_> = errors
+            .into_iter()
+            .map(|err| (err.kind, err.span.line, err.span.col))
+            .collect();
+        let unexpected = |expected, found| ParseErrorKind::Unexpected { expected, found };
+        assert_eq!(
+            vec![
+                (unexpected(Expected::Expression, Token::Eof), 1, 11),
+                (
+                    unexpected(
+                        Expected::Token(Token::RCurly),
+                        Token::Ident(String::from("b"))
+                    ),
+                    2,
+                    16
+                ),
+                (
+                    unexpected(Expected::Token(Token::RParent), Token::Eof),
+                    5,
+                    4
+                ),
+            ],
+            errors
+        );
+    }
+
+    /// Test for checking if annotations are rejected
+    /// while they can not be evaluated
+    #[test]
+    fn test_annotations() {
+        let mut lexer = Lexer::from_string("@test\nx :: 1 as Int", None);
+        let mut parser = Parser::new(&mut lexer);
+        let errors = parser.parse_program().expect_err("Parsed annotation");
+        let errors: Vec<_> = errors
+            .into_iter()
+            .map(|err| (err.kind, err.span.line, err.span.col))
+            .collect();
+        assert_eq!(
+            vec![(
+                ParseErrorKind::Unexpected {
+                    expected: Expected::Expression,
+                    found: Token::At,
+                },
+                1,
+                1
+            )],
+            errors
+        );
+    }
+
+    /// Test for checking if errors are displayed with their location
+    #[test]
+    fn test_error_display() {
+        let mut lexer = Lexer::from_string("if x 1", Some("display.nx"));
+        let mut parser = Parser::new(&mut lexer);
+        let err = parser.parse_stmt().expect_err("Parsed invalid statement");
+        assert_eq!("Expected `{`, found `1` at display.nx:1:6", err.to_string());
+    }
 }